@@ -34,10 +34,10 @@
               --replace-fail "/usr/sh" "${pkgs.bash}/bin/bash"
 
             substituteInPlace extra/config.toml \
-              --replace-fail "/usr/bin/X" "${pkgs.xorg.xorgserver}/bin/X"
+              --replace-fail '"/usr/bin/X"' '"${pkgs.xorg.xorgserver}/bin/X"'
 
             substituteInPlace extra/config.toml \
-              --replace-fail "/usr/bin/xauth" "${pkgs.xorg.xauth}/bin/xauth"
+              --replace-fail '"/usr/bin/Xwayland"' '"${pkgs.xwayland}/bin/Xwayland"'
           '';
 
           buildInputs = [
@@ -40,6 +40,15 @@ echo 'Copy over default configuration'
 $ROOT_CMD cp -f "extra/config.toml" "/etc/lemurs/config.toml"
 if [ $? -ne 0 ]; then exit 1; fi
 
+# Create the directory for custom themes
+$ROOT_CMD mkdir -p "/etc/lemurs/themes"
+
+# Copy over message catalogs
+echo 'Copy over message catalogs'
+$ROOT_CMD mkdir -p "/usr/share/lemurs/locale"
+$ROOT_CMD cp -f extra/locale/*.toml "/usr/share/lemurs/locale/"
+if [ $? -ne 0 ]; then exit 1; fi
+
 # Copy over xsetup
 echo 'Copy over more files'
 $ROOT_CMD cp -f "extra/xsetup.sh" "/etc/lemurs/xsetup.sh"
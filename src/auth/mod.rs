@@ -1,17 +1,17 @@
 mod pam;
 pub mod utmpx;
 
-use ::pam::{Authenticator, PasswordConv};
 use log::info;
 
-use crate::auth::pam::open_session;
 pub use crate::auth::pam::AuthenticationError;
+use crate::auth::pam::{open_session, PamSession};
+use crate::env_container::EnvironmentContainer;
 
-pub struct AuthUserInfo<'a> {
+pub struct AuthUserInfo {
     // This is used to keep the user session. If the struct is dropped then the user session is
     // also automatically dropped.
     #[allow(dead_code)]
-    authenticator: Authenticator<'a, PasswordConv>,
+    session: PamSession,
 
     #[allow(dead_code)]
     pub username: String,
@@ -23,14 +23,15 @@ pub struct AuthUserInfo<'a> {
     pub shell: String,
 }
 
-pub fn try_auth<'a>(
+pub fn try_auth(
     username: &str,
     password: &str,
     pam_service: &str,
-) -> Result<AuthUserInfo<'a>, AuthenticationError> {
+    process_env: &mut EnvironmentContainer,
+) -> Result<AuthUserInfo, AuthenticationError> {
     info!("Login attempt for '{username}'");
 
-    open_session(username, password, pam_service).map_err(|err| {
+    open_session(username, password, pam_service, process_env).map_err(|err| {
         info!(
             "Authentication failed for '{}'. Reason: {}",
             username,
@@ -1,11 +1,27 @@
+use std::ffi::{CStr, CString};
 use std::fmt;
+use std::mem;
+use std::ptr;
 
-use log::info;
-
-use pam::Authenticator;
+use libc::{c_char, c_int, c_void};
+use log::{info, warn};
+use pam_sys::{
+    PamConversation, PamFlag, PamHandle, PamMessage, PamMessageStyle, PamResponse, PamReturnCode,
+};
 use uzers::os::unix::UserExt;
 
 use crate::auth::AuthUserInfo;
+use crate::env_container::EnvironmentContainer;
+
+/// The environment variables that are handed to PAM before the session is opened. Modules such as
+/// `pam_systemd` use these to register the session.
+const PAM_SESSION_VARIABLES: [&str; 5] = [
+    "XDG_SESSION_TYPE",
+    "XDG_SESSION_CLASS",
+    "XDG_SEAT",
+    "XDG_VTNR",
+    "DISPLAY",
+];
 
 /// All the different errors that can occur during PAM opening an authenticated session
 #[derive(Clone)]
@@ -31,28 +47,299 @@ impl fmt::Display for AuthenticationError {
     }
 }
 
+/// The credentials that are given to PAM in the conversation
+struct Credentials {
+    username: CString,
+    /// The password is only needed for authentication and removed afterwards
+    password: Option<CString>,
+}
+
+impl Credentials {
+    /// Overwrite the password in memory and drop it
+    fn forget_password(&mut self) {
+        let Some(password) = self.password.take() else {
+            return;
+        };
+
+        let mut bytes = password.into_bytes_with_nul();
+        for byte in bytes.iter_mut() {
+            // A volatile write cannot be optimized away, even though the bytes are dropped next
+            // SAFETY: `byte` is a valid and aligned reference into the owned buffer
+            unsafe { ptr::write_volatile(byte, 0) };
+        }
+    }
+}
+
+impl Drop for Credentials {
+    fn drop(&mut self) {
+        self.forget_password();
+    }
+}
+
+/// Free the responses allocated by `converse` when the conversation fails
+///
+/// # Safety
+///
+/// `responses` has to be allocated by `converse` with room for `num_msg` responses.
+unsafe fn free_responses(responses: *mut PamResponse, num_msg: usize) {
+    for i in 0..num_msg {
+        // SAFETY: `responses` has room for `num_msg` zeroed or filled responses, whose `resp` is
+        // either null or allocated with `strdup`
+        libc::free((*responses.add(i)).resp as *mut c_void);
+    }
+
+    libc::free(responses as *mut c_void);
+}
+
+/// Answer the prompts of PAM with the username and password
+///
+/// The conversation fails on messages of an unknown style, e.g. binary prompts, and when PAM asks
+/// for the password after it has been forgotten.
+extern "C" fn converse(
+    num_msg: c_int,
+    msg: *mut *mut PamMessage,
+    out_resp: *mut *mut PamResponse,
+    appdata_ptr: *mut c_void,
+) -> c_int {
+    const PROMPT_ECHO_ON: c_int = PamMessageStyle::PROMPT_ECHO_ON as c_int;
+    const PROMPT_ECHO_OFF: c_int = PamMessageStyle::PROMPT_ECHO_OFF as c_int;
+    const TEXT_INFO: c_int = PamMessageStyle::TEXT_INFO as c_int;
+    const ERROR_MSG: c_int = PamMessageStyle::ERROR_MSG as c_int;
+
+    let Ok(num_msg) = usize::try_from(num_msg) else {
+        return PamReturnCode::CONV_ERR as c_int;
+    };
+
+    if msg.is_null() || out_resp.is_null() || appdata_ptr.is_null() {
+        return PamReturnCode::CONV_ERR as c_int;
+    }
+
+    // PAM frees the responses, so they need to be allocated with `calloc` and `strdup`
+    // SAFETY: `calloc` checks the size for overflows and returns either null or zeroed memory
+    let responses =
+        unsafe { libc::calloc(num_msg, mem::size_of::<PamResponse>()) as *mut PamResponse };
+    if responses.is_null() {
+        return PamReturnCode::BUF_ERR as c_int;
+    }
+
+    // SAFETY: `appdata_ptr` is the non-null pointer to the boxed credentials given to `pam_start`,
+    // which outlive the PAM handle
+    let credentials = unsafe { &*(appdata_ptr as *const Credentials) };
+
+    for i in 0..num_msg {
+        // SAFETY: PAM passes an array of `num_msg` message pointers, which was checked to be non-null
+        let message = unsafe { *msg.add(i) };
+        if message.is_null() {
+            // SAFETY: `responses` was allocated above with room for `num_msg` responses
+            unsafe { free_responses(responses, num_msg) };
+            return PamReturnCode::CONV_ERR as c_int;
+        }
+
+        // Styles are matched on their raw value, because pam-sys turns unknown styles into
+        // `ERROR_MSG`
+        // SAFETY: `message` is non-null and points to a message of PAM. `responses` has room for
+        // `num_msg` responses.
+        let (message, response) = unsafe { (&*message, &mut *responses.add(i)) };
+        let answer = match message.msg_style {
+            PROMPT_ECHO_ON => &credentials.username,
+            PROMPT_ECHO_OFF => match credentials.password.as_ref() {
+                Some(password) => password,
+                None => {
+                    warn!("PAM asked for the password after it was forgotten");
+                    // SAFETY: `responses` was allocated above with room for `num_msg` responses
+                    unsafe { free_responses(responses, num_msg) };
+                    return PamReturnCode::CONV_ERR as c_int;
+                }
+            },
+            TEXT_INFO => continue,
+            ERROR_MSG => {
+                if !message.msg.is_null() {
+                    // SAFETY: PAM messages are non-null nul-terminated strings
+                    let text = unsafe { CStr::from_ptr(message.msg) };
+                    warn!("PAM error: {}", text.to_string_lossy());
+                }
+                continue;
+            }
+            style => {
+                warn!("PAM sent a message of the unsupported style {style}");
+                // SAFETY: `responses` was allocated above with room for `num_msg` responses
+                unsafe { free_responses(responses, num_msg) };
+                return PamReturnCode::CONV_ERR as c_int;
+            }
+        };
+
+        // SAFETY: `answer` is a nul-terminated string
+        response.resp = unsafe { libc::strdup(answer.as_ptr()) };
+        if response.resp.is_null() {
+            // SAFETY: `responses` was allocated above with room for `num_msg` responses
+            unsafe { free_responses(responses, num_msg) };
+            return PamReturnCode::BUF_ERR as c_int;
+        }
+    }
+
+    // SAFETY: `out_resp` was checked to be non-null. PAM takes ownership of the responses.
+    unsafe { *out_resp = responses };
+
+    PamReturnCode::SUCCESS as c_int
+}
+
+/// An opened PAM session. The session is closed when this is dropped.
+pub struct PamSession {
+    handle: *mut PamHandle,
+    has_credentials: bool,
+    has_open_session: bool,
+    last_code: PamReturnCode,
+
+    // PAM keeps a pointer to the credentials for the conversation. So they need to outlive the
+    // handle and stay at the same address.
+    credentials: Box<Credentials>,
+}
+
+impl PamSession {
+    fn start(pam_service: &str, username: &str, password: &str) -> Option<Self> {
+        let credentials = Box::new(Credentials {
+            username: CString::new(username).ok()?,
+            password: Some(CString::new(password).ok()?),
+        });
+
+        let conversation = PamConversation {
+            conv: Some(converse),
+            data_ptr: &*credentials as *const Credentials as *mut c_void,
+        };
+
+        let mut handle: *mut PamHandle = ptr::null_mut();
+        match pam_sys::start(pam_service, None, &conversation, &mut handle) {
+            PamReturnCode::SUCCESS if !handle.is_null() => Some(Self {
+                handle,
+                has_credentials: false,
+                has_open_session: false,
+                last_code: PamReturnCode::SUCCESS,
+                credentials,
+            }),
+            code => {
+                // A handle can be returned even though starting failed
+                if !handle.is_null() {
+                    // SAFETY: `handle` is non-null and was created by `pam_start`
+                    pam_sys::end(unsafe { &mut *handle }, code);
+                }
+
+                None
+            }
+        }
+    }
+
+    fn handle(&mut self) -> &mut PamHandle {
+        // SAFETY: The handle is checked to be non-null on creation and lives until `pam_end`
+        unsafe { &mut *self.handle }
+    }
+
+    fn check(&mut self, code: PamReturnCode) -> Result<(), PamReturnCode> {
+        self.last_code = code;
+
+        if code == PamReturnCode::SUCCESS {
+            Ok(())
+        } else {
+            Err(code)
+        }
+    }
+
+    fn authenticate(&mut self) -> Result<(), PamReturnCode> {
+        let code = pam_sys::authenticate(self.handle(), PamFlag::NONE);
+        self.credentials.forget_password();
+        self.check(code)?;
+
+        let code = pam_sys::acct_mgmt(self.handle(), PamFlag::NONE);
+        self.check(code)
+    }
+
+    fn open(&mut self) -> Result<(), PamReturnCode> {
+        let code = pam_sys::setcred(self.handle(), PamFlag::ESTABLISH_CRED);
+        self.check(code)?;
+
+        self.has_credentials = true;
+
+        let code = pam_sys::open_session(self.handle(), PamFlag::NONE);
+        self.check(code)?;
+
+        self.has_open_session = true;
+
+        // Follow openSSH and call pam_setcred before and after open_session
+        let code = pam_sys::setcred(self.handle(), PamFlag::REINITIALIZE_CRED);
+        self.check(code)
+    }
+
+    fn putenv(&mut self, key: &str, value: &str) {
+        let code = pam_sys::putenv(self.handle(), &format!("{key}={value}"));
+
+        if code != PamReturnCode::SUCCESS {
+            warn!("Failed to hand environment variable '{key}' to PAM. Reason: {code}");
+        }
+    }
+
+    /// Get the environment variables that were set by the PAM modules
+    fn environment(&mut self) -> Vec<(String, String)> {
+        let list = pam_sys::getenvlist(self.handle()) as *mut *mut c_char;
+        if list.is_null() {
+            return Vec::new();
+        }
+
+        let mut variables = Vec::new();
+
+        // SAFETY: `pam_getenvlist` returns a null-terminated array of nul-terminated strings. The
+        // list and all its entries are owned by us and freed exactly once.
+        unsafe {
+            let mut entry = list;
+            while !(*entry).is_null() {
+                let variable = CStr::from_ptr(*entry).to_string_lossy();
+                if let Some((key, value)) = variable.split_once('=') {
+                    variables.push((key.to_string(), value.to_string()));
+                }
+
+                libc::free(*entry as *mut c_void);
+                entry = entry.add(1);
+            }
+
+            libc::free(list as *mut c_void);
+        }
+
+        variables
+    }
+}
+
+impl Drop for PamSession {
+    fn drop(&mut self) {
+        if self.has_open_session {
+            pam_sys::close_session(self.handle(), PamFlag::NONE);
+        }
+
+        if self.has_credentials {
+            pam_sys::setcred(self.handle(), PamFlag::DELETE_CRED);
+        }
+
+        let last_code = self.last_code;
+        pam_sys::end(self.handle(), last_code);
+    }
+}
+
 /// Open a PAM authenticated session
-pub fn open_session<'a>(
+///
+/// The session variables of `process_env` are handed to PAM and the variables that PAM sets are
+/// added to `process_env`. The environment of Lemurs itself is not changed.
+pub fn open_session(
     username: &str,
     password: &str,
     pam_service: &str,
-) -> Result<AuthUserInfo<'a>, AuthenticationError> {
+    process_env: &mut EnvironmentContainer,
+) -> Result<AuthUserInfo, AuthenticationError> {
     info!("Started opening session");
 
-    let mut authenticator = Authenticator::with_password(pam_service)
-        .map_err(|_| AuthenticationError::PamService(pam_service.to_string()))?;
+    let mut session = PamSession::start(pam_service, username, password)
+        .ok_or_else(|| AuthenticationError::PamService(pam_service.to_string()))?;
 
     info!("Gotten Authenticator");
 
-    // Authenticate the user
-    authenticator
-        .get_handler()
-        .set_credentials(username, password);
-
-    info!("Got handler");
-
     // Validate the account
-    authenticator
+    session
         .authenticate()
         .map_err(|_| AuthenticationError::AccountValidation)?;
 
@@ -76,15 +363,25 @@ pub fn open_session<'a>(
         .ok_or(AuthenticationError::ShellInvalidUtf8)?
         .to_string();
 
-    authenticator
-        .open_session()
+    for key in PAM_SESSION_VARIABLES {
+        if let Some(value) = process_env.get(key) {
+            session.putenv(key, value);
+        }
+    }
+
+    session
+        .open()
         .map_err(|_| AuthenticationError::SessionOpen)?;
 
     info!("Opened session");
 
-    // NOTE: Logout happens automatically here with `drop` of authenticator
+    for (key, value) in session.environment() {
+        process_env.set(key, value);
+    }
+
+    // NOTE: Logout happens automatically here with `drop` of session
     Ok(AuthUserInfo {
-        authenticator,
+        session,
 
         username: username.to_string(),
         uid,
@@ -94,3 +391,139 @@ pub fn open_session<'a>(
         shell,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROMPT_ECHO_ON: c_int = PamMessageStyle::PROMPT_ECHO_ON as c_int;
+    const PROMPT_ECHO_OFF: c_int = PamMessageStyle::PROMPT_ECHO_OFF as c_int;
+    const TEXT_INFO: c_int = PamMessageStyle::TEXT_INFO as c_int;
+    /// `PAM_BINARY_PROMPT` of Linux-PAM, which is not supported
+    const BINARY_PROMPT: c_int = 7;
+
+    fn credentials() -> Credentials {
+        Credentials {
+            username: CString::new("alice").unwrap(),
+            password: Some(CString::new("hunter2").unwrap()),
+        }
+    }
+
+    fn message(msg_style: c_int, text: &CStr) -> PamMessage {
+        PamMessage {
+            msg_style,
+            msg: text.as_ptr(),
+        }
+    }
+
+    /// Run the conversation on `messages` and collect the text of the responses
+    fn run_conversation(
+        credentials: &Credentials,
+        messages: &mut [*mut PamMessage],
+    ) -> Result<Vec<Option<String>>, c_int> {
+        let mut responses: *mut PamResponse = ptr::null_mut();
+        let code = converse(
+            messages.len() as c_int,
+            messages.as_mut_ptr(),
+            &mut responses,
+            credentials as *const Credentials as *mut c_void,
+        );
+
+        if code != PamReturnCode::SUCCESS as c_int {
+            assert!(responses.is_null());
+            return Err(code);
+        }
+
+        let texts = (0..messages.len())
+            .map(|i| {
+                // SAFETY: On success, there is a response for every message, whose `resp` is
+                // either null or a string allocated with `strdup`
+                let resp = unsafe { (*responses.add(i)).resp };
+                (!resp.is_null())
+                    // SAFETY: `resp` is a non-null nul-terminated string
+                    .then(|| {
+                        unsafe { CStr::from_ptr(resp) }
+                            .to_string_lossy()
+                            .into_owned()
+                    })
+            })
+            .collect();
+
+        // SAFETY: The responses were allocated by `converse` for this many messages
+        unsafe { free_responses(responses, messages.len()) };
+
+        Ok(texts)
+    }
+
+    #[test]
+    fn echo_on_prompt_is_answered_with_username() {
+        let prompt_text = CString::new("login:").unwrap();
+        let mut prompt = message(PROMPT_ECHO_ON, &prompt_text);
+
+        assert_eq!(
+            run_conversation(&credentials(), &mut [&mut prompt]),
+            Ok(vec![Some("alice".to_string())])
+        );
+    }
+
+    #[test]
+    fn echo_off_prompt_is_answered_with_password() {
+        let prompt_text = CString::new("Password:").unwrap();
+        let mut prompt = message(PROMPT_ECHO_OFF, &prompt_text);
+
+        assert_eq!(
+            run_conversation(&credentials(), &mut [&mut prompt]),
+            Ok(vec![Some("hunter2".to_string())])
+        );
+    }
+
+    #[test]
+    fn echo_off_prompt_fails_after_password_is_forgotten() {
+        let mut credentials = credentials();
+        credentials.forget_password();
+        let prompt_text = CString::new("Password:").unwrap();
+        let mut prompt = message(PROMPT_ECHO_OFF, &prompt_text);
+
+        assert_eq!(
+            run_conversation(&credentials, &mut [&mut prompt]),
+            Err(PamReturnCode::CONV_ERR as c_int)
+        );
+    }
+
+    #[test]
+    fn text_info_is_not_answered() {
+        let info_text = CString::new("Welcome").unwrap();
+        let mut info = message(TEXT_INFO, &info_text);
+        let prompt_text = CString::new("Password:").unwrap();
+        let mut prompt = message(PROMPT_ECHO_OFF, &prompt_text);
+
+        assert_eq!(
+            run_conversation(&credentials(), &mut [&mut info, &mut prompt]),
+            Ok(vec![None, Some("hunter2".to_string())])
+        );
+    }
+
+    #[test]
+    fn unknown_style_fails_the_conversation() {
+        let prompt_text = CString::new("login:").unwrap();
+        let mut prompt = message(PROMPT_ECHO_ON, &prompt_text);
+        let binary_text = CString::new("").unwrap();
+        let mut binary = message(BINARY_PROMPT, &binary_text);
+
+        assert_eq!(
+            run_conversation(&credentials(), &mut [&mut prompt, &mut binary]),
+            Err(PamReturnCode::CONV_ERR as c_int)
+        );
+    }
+
+    #[test]
+    fn null_message_fails_the_conversation() {
+        let prompt_text = CString::new("login:").unwrap();
+        let mut prompt = message(PROMPT_ECHO_ON, &prompt_text);
+
+        assert_eq!(
+            run_conversation(&credentials(), &mut [&mut prompt, ptr::null_mut()]),
+            Err(PamReturnCode::CONV_ERR as c_int)
+        );
+    }
+}
@@ -1,3 +1,16 @@
+/// The utmpx functions of glibc share a single file handle and are not thread-safe, while the UI
+/// reads the records as a session is added or removed. All of them go through this lock.
+#[cfg(target_env = "gnu")]
+static UTMPX_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+#[cfg(target_env = "gnu")]
+fn lock_utmpx() -> std::sync::MutexGuard<'static, ()> {
+    // The lock protects no data, so a panic while it was held leaves nothing inconsistent
+    UTMPX_LOCK
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
 pub struct UtmpxSession {
     #[cfg(target_env = "gnu")]
     session: libc::utmpx,
@@ -69,9 +82,11 @@ pub fn add_utmpx_entry(username: &str, tty: u8, pid: u32) -> UtmpxSession {
         s
     };
 
+    let _lock = lock_utmpx();
     unsafe {
         libc::setutxent();
         libc::pututxline(&entry as *const libc::utmpx);
+        libc::endutxent();
     };
 
     log::info!("Added UTMPX record");
@@ -101,6 +116,7 @@ impl Drop for UtmpxSession {
         entry.ut_tv.tv_usec = 0;
         entry.ut_tv.tv_sec = 0;
 
+        let _lock = lock_utmpx();
         unsafe {
             libc::setutxent();
             libc::pututxline(&entry as *const libc::utmpx);
@@ -108,3 +124,34 @@ impl Drop for UtmpxSession {
         }
     }
 }
+
+/// Count the users that are logged in according to the UTMPX records
+#[cfg(target_env = "gnu")]
+pub fn logged_in_users() -> usize {
+    let mut count = 0;
+
+    let _lock = lock_utmpx();
+    unsafe {
+        libc::setutxent();
+
+        loop {
+            let entry = libc::getutxent();
+            if entry.is_null() {
+                break;
+            }
+
+            if (*entry).ut_type == libc::USER_PROCESS {
+                count += 1;
+            }
+        }
+
+        libc::endutxent();
+    }
+
+    count
+}
+
+#[cfg(not(target_env = "gnu"))]
+pub fn logged_in_users() -> usize {
+    0
+}
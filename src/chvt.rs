@@ -16,6 +16,30 @@ use std::fmt::{self, Debug, Display, Formatter};
 const VT_ACTIVATE: RequestType = 0x5606;
 const VT_WAITACTIVE: RequestType = 0x5607;
 
+// Request Number for the Linux specific console functions and its subcodes to (un)blank the screen
+const TIOCLINUX: RequestType = 0x541C;
+const TIOCL_UNBLANKSCREEN: u8 = 4;
+const TIOCL_BLANKSCREEN: u8 = 14;
+
+// Request Numbers to get and set the 16 colors of the console
+const GIO_CMAP: RequestType = 0x4B70;
+const PIO_CMAP: RequestType = 0x4B71;
+
+// Request Numbers to get and set the font of the console and the mapping of unicode onto its glyphs
+const KDFONTOP: RequestType = 0x4B72;
+const GIO_UNIMAP: RequestType = 0x4B66;
+const PIO_UNIMAP: RequestType = 0x4B67;
+const PIO_UNIMAPCLR: RequestType = 0x4B68;
+
+const KD_FONT_OP_SET: libc::c_uint = 0;
+const KD_FONT_OP_GET: libc::c_uint = 1;
+
+/// The kernel stores the glyphs of a font with a fixed amount of rows
+pub const FONT_GLYPH_ROWS: usize = 32;
+/// The largest amount of glyphs and width of a console font
+const MAX_FONT_GLYPHS: usize = 512;
+const MAX_FONT_WIDTH: usize = 32;
+
 // Request Number to get Keyboard Type
 const KDGKBTYPE: RequestType = 0x4B33;
 
@@ -26,6 +50,9 @@ const KB_84: u8 = 0x01;
 pub enum ChvtError {
     Activate,
     WaitActive,
+    Blank,
+    Palette,
+    Font,
     Close,
     OpenConsole,
     NotAConsole,
@@ -39,7 +66,50 @@ impl Display for ChvtError {
     }
 }
 
-fn is_a_console(fd: c_int) -> bool {
+#[repr(C)]
+struct ConsoleFontOp {
+    op: libc::c_uint,
+    flags: libc::c_uint,
+    width: libc::c_uint,
+    height: libc::c_uint,
+    charcount: libc::c_uint,
+    data: *mut u8,
+}
+
+#[repr(C)]
+struct UnimapDesc {
+    entry_ct: libc::c_ushort,
+    entries: *mut UniPair,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct UnimapInit {
+    advised_hashsize: libc::c_ushort,
+    advised_hashstep: libc::c_ushort,
+    advised_hashlevel: libc::c_ushort,
+}
+
+/// A unicode code point that is shown with the glyph at `fontpos`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UniPair {
+    pub unicode: u16,
+    pub fontpos: u16,
+}
+
+/// The glyphs of a console font
+///
+/// Every glyph takes [`FONT_GLYPH_ROWS`] rows of `(width + 7) / 8` bytes, no matter its height.
+#[derive(Debug, Clone)]
+pub struct ConsoleFontData {
+    pub width: u32,
+    pub height: u32,
+    pub charcount: u32,
+    pub data: Vec<u8>,
+}
+
+pub fn is_a_console(fd: c_int) -> bool {
     let mut arg = 0;
     if unsafe { libc::ioctl(fd, KDGKBTYPE, &mut arg) } > 0 {
         return false;
@@ -115,3 +185,152 @@ pub unsafe fn chvt(ttynum: i32) -> Result<(), ChvtError> {
 
     Ok(())
 }
+
+/// Blank or unblank the screen of the virtual console
+///
+/// A blanked screen is not unblanked by key presses, so it should always be unblanked again.
+pub fn set_screen_blanked(blanked: bool) -> Result<(), ChvtError> {
+    let fd = get_fd()?;
+
+    let mut subcode = if blanked {
+        TIOCL_BLANKSCREEN
+    } else {
+        TIOCL_UNBLANKSCREEN
+    };
+
+    let result = unsafe { libc::ioctl(fd, TIOCLINUX, &mut subcode) };
+
+    close(fd).map_err(|_| ChvtError::Close)?;
+
+    if result < 0 {
+        return Err(ChvtError::Blank);
+    }
+
+    Ok(())
+}
+
+/// Get the 16 colors of the console as consecutive red, green and blue values
+pub fn get_console_palette(fd: c_int) -> Result<[u8; 48], ChvtError> {
+    let mut palette = [0u8; 48];
+
+    if unsafe { libc::ioctl(fd, GIO_CMAP, palette.as_mut_ptr()) } < 0 {
+        return Err(ChvtError::Palette);
+    }
+
+    Ok(palette)
+}
+
+/// Set the 16 colors of the console from consecutive red, green and blue values
+///
+/// This changes the colors of all virtual consoles.
+pub fn set_console_palette(fd: c_int, palette: &[u8; 48]) -> Result<(), ChvtError> {
+    if unsafe { libc::ioctl(fd, PIO_CMAP, palette.as_ptr()) } < 0 {
+        return Err(ChvtError::Palette);
+    }
+
+    Ok(())
+}
+
+/// Get the font of the console
+pub fn get_console_font(fd: c_int) -> Result<ConsoleFontData, ChvtError> {
+    let mut data = vec![0u8; MAX_FONT_GLYPHS * FONT_GLYPH_ROWS * MAX_FONT_WIDTH / 8];
+    let mut font_op = ConsoleFontOp {
+        op: KD_FONT_OP_GET,
+        flags: 0,
+        width: MAX_FONT_WIDTH as libc::c_uint,
+        height: FONT_GLYPH_ROWS as libc::c_uint,
+        charcount: MAX_FONT_GLYPHS as libc::c_uint,
+        data: data.as_mut_ptr(),
+    };
+
+    if unsafe { libc::ioctl(fd, KDFONTOP, &mut font_op) } < 0 {
+        return Err(ChvtError::Font);
+    }
+
+    let glyph_size = (font_op.width as usize + 7) / 8 * FONT_GLYPH_ROWS;
+    data.truncate(glyph_size * font_op.charcount as usize);
+
+    Ok(ConsoleFontData {
+        width: font_op.width,
+        height: font_op.height,
+        charcount: font_op.charcount,
+        data,
+    })
+}
+
+/// Set the font of the console
+///
+/// The console is resized to the amount of rows and columns that fit on the screen with the font.
+pub fn set_console_font(fd: c_int, font: &ConsoleFontData) -> Result<(), ChvtError> {
+    let glyph_size = (font.width as usize + 7) / 8 * FONT_GLYPH_ROWS;
+    if font.data.len() < glyph_size * font.charcount as usize {
+        return Err(ChvtError::Font);
+    }
+
+    // The kernel only reads from the data when setting the font
+    let mut font_op = ConsoleFontOp {
+        op: KD_FONT_OP_SET,
+        flags: 0,
+        width: font.width,
+        height: font.height,
+        charcount: font.charcount,
+        data: font.data.as_ptr() as *mut u8,
+    };
+
+    if unsafe { libc::ioctl(fd, KDFONTOP, &mut font_op) } < 0 {
+        return Err(ChvtError::Font);
+    }
+
+    Ok(())
+}
+
+/// Get the mapping of unicode code points onto the glyphs of the console font
+pub fn get_unicode_map(fd: c_int) -> Result<Vec<UniPair>, ChvtError> {
+    // Ask for the size of the mapping first
+    let mut descriptor = UnimapDesc {
+        entry_ct: 0,
+        entries: std::ptr::null_mut(),
+    };
+    if unsafe { libc::ioctl(fd, GIO_UNIMAP, &mut descriptor) } < 0 && Errno::last() != Errno::ENOMEM
+    {
+        return Err(ChvtError::Font);
+    }
+
+    let mut entries = vec![
+        UniPair {
+            unicode: 0,
+            fontpos: 0
+        };
+        descriptor.entry_ct.into()
+    ];
+    descriptor.entries = entries.as_mut_ptr();
+
+    if unsafe { libc::ioctl(fd, GIO_UNIMAP, &mut descriptor) } < 0 {
+        return Err(ChvtError::Font);
+    }
+
+    entries.truncate(descriptor.entry_ct.into());
+    Ok(entries)
+}
+
+/// Replace the mapping of unicode code points onto the glyphs of the console font
+pub fn set_unicode_map(fd: c_int, entries: &[UniPair]) -> Result<(), ChvtError> {
+    let entry_ct = libc::c_ushort::try_from(entries.len()).map_err(|_| ChvtError::Font)?;
+
+    let mut init = UnimapInit::default();
+    if unsafe { libc::ioctl(fd, PIO_UNIMAPCLR, &mut init) } < 0 {
+        return Err(ChvtError::Font);
+    }
+
+    // The kernel only reads from the entries when setting the mapping
+    let mut descriptor = UnimapDesc {
+        entry_ct,
+        entries: entries.as_ptr() as *mut UniPair,
+    };
+
+    if unsafe { libc::ioctl(fd, PIO_UNIMAP, &mut descriptor) } < 0 {
+        return Err(ChvtError::Font);
+    }
+
+    Ok(())
+}
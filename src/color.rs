@@ -0,0 +1,449 @@
+//! This module implements the parsing of colors and their downgrading to what the console can show.
+//!
+//! The Linux virtual console only shows 16 colors, so colors from the configuration are mapped to
+//! the nearest color the console can actually show.
+
+use std::env;
+use std::sync::OnceLock;
+
+use log::{info, warn};
+use ratatui::style::Color;
+
+use crate::chvt::{get_console_palette, is_a_console, set_console_palette};
+
+/// The colors that can be referred to by name, apart from the 16 ANSI colors
+const NAMED_COLORS: [(&str, (u8, u8, u8)); 36] = [
+    ("orange", (255, 127, 0)),
+    ("dark orange", (255, 140, 0)),
+    ("dark red", (139, 0, 0)),
+    ("dark green", (0, 100, 0)),
+    ("dark blue", (0, 0, 139)),
+    ("dark cyan", (0, 139, 139)),
+    ("dark magenta", (139, 0, 139)),
+    ("maroon", (128, 0, 0)),
+    ("olive", (128, 128, 0)),
+    ("navy", (0, 0, 128)),
+    ("purple", (128, 0, 128)),
+    ("teal", (0, 128, 128)),
+    ("silver", (192, 192, 192)),
+    ("lime", (0, 255, 0)),
+    ("aqua", (0, 255, 255)),
+    ("fuchsia", (255, 0, 255)),
+    ("pink", (255, 192, 203)),
+    ("hot pink", (255, 105, 180)),
+    ("brown", (165, 42, 42)),
+    ("chocolate", (210, 105, 30)),
+    ("tan", (210, 180, 140)),
+    ("beige", (245, 245, 220)),
+    ("gold", (255, 215, 0)),
+    ("khaki", (240, 230, 140)),
+    ("coral", (255, 127, 80)),
+    ("salmon", (250, 128, 114)),
+    ("tomato", (255, 99, 71)),
+    ("crimson", (220, 20, 60)),
+    ("violet", (238, 130, 238)),
+    ("indigo", (75, 0, 130)),
+    ("lavender", (230, 230, 250)),
+    ("sky blue", (135, 206, 235)),
+    ("steel blue", (70, 130, 180)),
+    ("royal blue", (65, 105, 225)),
+    ("turquoise", (64, 224, 208)),
+    ("sea green", (46, 139, 87)),
+];
+
+/// The 16 ANSI colors with the values the Linux virtual console uses for them
+const ANSI_COLORS: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (170, 0, 0)),
+    (Color::Green, (0, 170, 0)),
+    (Color::Yellow, (170, 85, 0)),
+    (Color::Blue, (0, 0, 170)),
+    (Color::Magenta, (170, 0, 170)),
+    (Color::Cyan, (0, 170, 170)),
+    (Color::Gray, (170, 170, 170)),
+    (Color::DarkGray, (85, 85, 85)),
+    (Color::LightRed, (255, 85, 85)),
+    (Color::LightGreen, (85, 255, 85)),
+    (Color::LightYellow, (255, 255, 85)),
+    (Color::LightBlue, (85, 85, 255)),
+    (Color::LightMagenta, (255, 85, 255)),
+    (Color::LightCyan, (85, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+/// The levels of the color cube of the 256 color palette
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// The amount of colors that the terminal can show
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorCapability {
+    Ansi16,
+    Indexed256,
+    TrueColor,
+}
+
+static CAPABILITY: OnceLock<ColorCapability> = OnceLock::new();
+
+/// The values of the 16 colors of the console once its palette has been programmed
+static CONSOLE_COLORS: OnceLock<[(u8, u8, u8); 16]> = OnceLock::new();
+
+fn parse_hex(hex: &str) -> Option<Color> {
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let channel = |digits: &str| u8::from_str_radix(digits, 16).ok();
+
+    match hex.len() {
+        // Every digit of `#rgb` is repeated, e.g. `#f80` is `#ff8800`
+        3 => {
+            let digits: Vec<u8> = hex
+                .chars()
+                .map(|c| channel(&c.to_string()).map(|value| value * 17))
+                .collect::<Option<_>>()?;
+            Some(Color::Rgb(digits[0], digits[1], digits[2]))
+        }
+        6 => Some(Color::Rgb(
+            channel(&hex[0..2])?,
+            channel(&hex[2..4])?,
+            channel(&hex[4..6])?,
+        )),
+        _ => None,
+    }
+}
+
+/// Get the arguments of a function-like color such as `rgb(255, 127, 0)`
+fn function_arguments<'a>(color: &'a str, name: &str) -> Option<Vec<&'a str>> {
+    let arguments = color
+        .strip_prefix(name)?
+        .trim_start()
+        .strip_prefix('(')?
+        .strip_suffix(')')?;
+
+    Some(arguments.split(',').map(str::trim).collect())
+}
+
+/// Parse a color from the configuration
+///
+/// This accepts the ANSI color names, the names in [`NAMED_COLORS`], `#rgb`, `#rrggbb`,
+/// `rgb(r, g, b)`, `indexed(n)` for the 256 color palette and `reset` or `default` for the
+/// default color of the terminal.
+pub fn parse_color(color: &str) -> Option<Color> {
+    use Color::*;
+
+    // Both spellings of gray are accepted
+    let c = color.trim().to_lowercase().replace("grey", "gray");
+
+    Some(match &c[..] {
+        "reset" | "default" => Reset,
+
+        // TUI colors
+        "black" => Black,
+        "red" => Red,
+        "green" => Green,
+        "yellow" => Yellow,
+        "blue" => Blue,
+        "magenta" => Magenta,
+        "cyan" => Cyan,
+        "gray" => Gray,
+        "dark gray" => DarkGray,
+        "light red" => LightRed,
+        "light green" => LightGreen,
+        "light yellow" => LightYellow,
+        "light blue" => LightBlue,
+        "light magenta" => LightMagenta,
+        "light cyan" => LightCyan,
+        "white" => White,
+
+        c => {
+            if let Some(hex) = c.strip_prefix('#') {
+                return parse_hex(hex);
+            }
+
+            if let Some(arguments) = function_arguments(c, "rgb") {
+                let [r, g, b] = arguments[..] else {
+                    return None;
+                };
+                return Some(Rgb(r.parse().ok()?, g.parse().ok()?, b.parse().ok()?));
+            }
+
+            if let Some(arguments) = function_arguments(c, "indexed") {
+                let [index] = arguments[..] else {
+                    return None;
+                };
+                return Some(Indexed(index.parse().ok()?));
+            }
+
+            let (_, (r, g, b)) = NAMED_COLORS.iter().find(|(name, _)| *name == c)?;
+            Rgb(*r, *g, *b)
+        }
+    })
+}
+
+/// Get the color capability of the terminal Lemurs runs on
+fn detect_capability() -> ColorCapability {
+    // The Linux virtual console only has 16 colors, whatever `TERM` says
+    if is_a_console(libc::STDOUT_FILENO) {
+        return ColorCapability::Ansi16;
+    }
+
+    let term = env::var("TERM").unwrap_or_default();
+    let colorterm = env::var("COLORTERM").unwrap_or_default();
+
+    if matches!(&colorterm[..], "truecolor" | "24bit") || term.ends_with("-direct") {
+        ColorCapability::TrueColor
+    } else if term.contains("256color") {
+        ColorCapability::Indexed256
+    } else if term.is_empty() || term == "dumb" || term == "linux" || term.starts_with("vt") {
+        ColorCapability::Ansi16
+    } else {
+        ColorCapability::Indexed256
+    }
+}
+
+pub fn capability() -> ColorCapability {
+    *CAPABILITY.get_or_init(detect_capability)
+}
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let channel = |a: u8, b: u8| (i32::from(a) - i32::from(b)).unsigned_abs().pow(2);
+    channel(r1, r2) + channel(g1, g2) + channel(b1, b2)
+}
+
+/// Get the value of a color of the 256 color palette
+fn indexed_to_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => ANSI_COLORS[usize::from(index)].1,
+        16..=231 => {
+            let index = index - 16;
+            (
+                CUBE_LEVELS[usize::from(index / 36)],
+                CUBE_LEVELS[usize::from(index / 6 % 6)],
+                CUBE_LEVELS[usize::from(index % 6)],
+            )
+        }
+        _ => {
+            let level = 8 + (index - 232) * 10;
+            (level, level, level)
+        }
+    }
+}
+
+/// Get the red, green and blue values of a color
+///
+/// The default color of the terminal has no known value.
+pub fn to_rgb(color: Color) -> Option<(u8, u8, u8)> {
+    match color {
+        Color::Reset => None,
+        Color::Rgb(r, g, b) => Some((r, g, b)),
+        Color::Indexed(index) => Some(indexed_to_rgb(index)),
+        color => ANSI_COLORS
+            .iter()
+            .find(|(ansi, _)| *ansi == color)
+            .map(|(_, rgb)| *rgb),
+    }
+}
+
+/// The 16 ANSI colors with the values they are shown with
+fn ansi_values() -> [(Color, (u8, u8, u8)); 16] {
+    let mut values = ANSI_COLORS;
+
+    if let Some(console_colors) = CONSOLE_COLORS.get() {
+        for ((_, value), console_color) in values.iter_mut().zip(console_colors) {
+            *value = *console_color;
+        }
+    }
+
+    values
+}
+
+fn nearest_ansi(rgb: (u8, u8, u8)) -> Color {
+    ansi_values()
+        .iter()
+        .min_by_key(|(_, ansi)| distance(rgb, *ansi))
+        .map(|(color, _)| *color)
+        .unwrap_or(Color::White)
+}
+
+fn nearest_indexed(rgb: (u8, u8, u8)) -> Color {
+    let nearest_level = |value: u8| {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|level| CUBE_LEVELS[*level].abs_diff(value))
+            .unwrap_or(0) as u8
+    };
+
+    let (r, g, b) = rgb;
+    let cube_index = 16 + 36 * nearest_level(r) + 6 * nearest_level(g) + nearest_level(b);
+
+    let average = ((u16::from(r) + u16::from(g) + u16::from(b)) / 3) as u8;
+    let gray_index = 232 + (average.saturating_sub(3) / 10).min(23);
+
+    if distance(rgb, indexed_to_rgb(gray_index)) < distance(rgb, indexed_to_rgb(cube_index)) {
+        Color::Indexed(gray_index)
+    } else {
+        Color::Indexed(cube_index)
+    }
+}
+
+/// The palette of the Linux console after it was programmed with the colors of the theme
+pub struct ConsolePalette {
+    original: [u8; 48],
+    programmed: [u8; 48],
+}
+
+impl ConsolePalette {
+    /// Program the palette of the console with `colors` in the order of the ANSI colors
+    ///
+    /// Colors that are not given keep their value. Nothing is done when Lemurs does not run on the
+    /// Linux console.
+    pub fn program(colors: &[(u8, u8, u8)]) -> Option<Self> {
+        if colors.is_empty() || !is_a_console(libc::STDOUT_FILENO) {
+            return None;
+        }
+
+        let original = match get_console_palette(libc::STDOUT_FILENO) {
+            Ok(original) => original,
+            Err(err) => {
+                warn!("Failed to get the palette of the console. Reason: {err}");
+                return None;
+            }
+        };
+
+        let mut programmed = original;
+        for (index, (r, g, b)) in colors.iter().take(16).enumerate() {
+            programmed[index * 3..index * 3 + 3].copy_from_slice(&[*r, *g, *b]);
+        }
+
+        info!("Programming the palette of the console");
+        let palette = Self {
+            original,
+            programmed,
+        };
+        palette.apply();
+
+        // From now on, colors are matched against what the console actually shows
+        let mut console_colors = [(0, 0, 0); 16];
+        for (console_color, rgb) in console_colors.iter_mut().zip(programmed.chunks(3)) {
+            *console_color = (rgb[0], rgb[1], rgb[2]);
+        }
+        let _ = CONSOLE_COLORS.set(console_colors);
+
+        Some(palette)
+    }
+
+    /// Show the colors of the theme
+    pub fn apply(&self) {
+        if let Err(err) = set_console_palette(libc::STDOUT_FILENO, &self.programmed) {
+            warn!("Failed to program the palette of the console. Reason: {err}");
+        }
+    }
+
+    /// Show the colors the console had before it was programmed
+    pub fn restore(&self) {
+        if let Err(err) = set_console_palette(libc::STDOUT_FILENO, &self.original) {
+            warn!("Failed to restore the palette of the console. Reason: {err}");
+        }
+    }
+}
+
+/// Map `color` to the nearest color that can be shown with `capability`
+pub fn downgrade(color: Color, capability: ColorCapability) -> Color {
+    match (color, capability) {
+        (Color::Rgb(r, g, b), ColorCapability::Ansi16) => nearest_ansi((r, g, b)),
+        (Color::Rgb(r, g, b), ColorCapability::Indexed256) => nearest_indexed((r, g, b)),
+        (Color::Indexed(index), ColorCapability::Ansi16) => nearest_ansi(indexed_to_rgb(index)),
+        (color, _) => color,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ansi_color_names_ignore_case_and_spaces() {
+        assert_eq!(parse_color("Light Blue"), Some(Color::LightBlue));
+        assert_eq!(parse_color("dark grey"), Some(Color::DarkGray));
+        assert_eq!(parse_color("default"), Some(Color::Reset));
+    }
+
+    #[test]
+    fn css_color_names_are_rgb() {
+        assert_eq!(parse_color("orange"), Some(Color::Rgb(255, 127, 0)));
+        assert_eq!(parse_color("sky blue"), Some(Color::Rgb(135, 206, 235)));
+    }
+
+    #[test]
+    fn hex_colors_have_three_or_six_digits() {
+        assert_eq!(parse_color("#87CEEB"), Some(Color::Rgb(135, 206, 235)));
+        assert_eq!(parse_color("#f80"), Some(Color::Rgb(255, 136, 0)));
+        assert_eq!(parse_color("#12345"), None);
+        assert_eq!(parse_color("#gg0000"), None);
+    }
+
+    #[test]
+    fn rgb_colors_have_three_bytes() {
+        assert_eq!(parse_color("rgb(1, 2,3)"), Some(Color::Rgb(1, 2, 3)));
+        assert_eq!(parse_color("rgb(1, 2)"), None);
+        assert_eq!(parse_color("rgb(256, 0, 0)"), None);
+    }
+
+    #[test]
+    fn indexed_colors_are_within_the_palette() {
+        assert_eq!(parse_color("indexed(208)"), Some(Color::Indexed(208)));
+        assert_eq!(parse_color("indexed(256)"), None);
+    }
+
+    #[test]
+    fn unknown_color_names_are_invalid() {
+        assert_eq!(parse_color(""), None);
+        assert_eq!(parse_color("blu"), None);
+    }
+
+    #[test]
+    fn true_color_is_kept() {
+        assert_eq!(
+            downgrade(Color::Rgb(255, 127, 0), ColorCapability::TrueColor),
+            Color::Rgb(255, 127, 0)
+        );
+    }
+
+    #[test]
+    fn rgb_is_downgraded_to_the_nearest_ansi_color() {
+        use ColorCapability::Ansi16;
+
+        assert_eq!(downgrade(Color::Rgb(250, 250, 250), Ansi16), Color::White);
+        assert_eq!(downgrade(Color::Rgb(180, 10, 10), Ansi16), Color::Red);
+    }
+
+    #[test]
+    fn indexed_is_downgraded_to_the_nearest_ansi_color() {
+        assert_eq!(
+            downgrade(Color::Indexed(196), ColorCapability::Ansi16),
+            Color::Red
+        );
+    }
+
+    #[test]
+    fn rgb_is_downgraded_to_the_color_cube_or_grayscale() {
+        use ColorCapability::Indexed256;
+
+        assert_eq!(
+            downgrade(Color::Rgb(255, 0, 0), Indexed256),
+            Color::Indexed(196)
+        );
+        assert_eq!(
+            downgrade(Color::Rgb(128, 128, 128), Indexed256),
+            Color::Indexed(244)
+        );
+    }
+
+    #[test]
+    fn supported_colors_are_kept() {
+        assert_eq!(
+            downgrade(Color::Indexed(196), ColorCapability::Indexed256),
+            Color::Indexed(196)
+        );
+        assert_eq!(downgrade(Color::Blue, ColorCapability::Ansi16), Color::Blue);
+    }
+}
@@ -1,15 +1,19 @@
 use crossterm::event::KeyCode;
-use log::error;
+use log::{error, warn};
 use serde::{de::Error, Deserialize};
+use std::collections::HashMap;
 use std::fmt::Display;
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
 use std::process;
+use std::sync::OnceLock;
 use toml::Value;
 
 use ratatui::style::{Color, Modifier};
 
+use crate::color::{self, parse_color};
+
 #[derive(Debug)]
 pub struct VarError {
     variable: String,
@@ -28,58 +32,44 @@ impl Display for VarError {
     }
 }
 
+/// Settings that are no longer used, but are still accepted so older configurations keep loading
+const DEPRECATED_KEYS: [(&str, &str, &str); 1] = [(
+    "x11",
+    "xauth_path",
+    "The Xauthority files are written by lemurs itself",
+)];
+
+/// The palette of the theme that colors can refer to by name
+static PALETTE: OnceLock<Palette> = OnceLock::new();
+
+/// Set the palette that colors can refer to. This can only be done once.
+pub fn set_palette(palette: Palette) {
+    if PALETTE.set(palette).is_err() {
+        warn!("The palette was already set. Ignoring the new palette.");
+    }
+}
+
+/// Parse a color that may refer to the palette of the theme
+pub fn resolve_color(color: &str) -> Option<Color> {
+    let palette = PALETTE.get();
+    parse_color(color).or_else(|| palette.and_then(|palette| palette_to_color(color, palette)))
+}
+
 pub fn get_color(color: &str) -> Color {
-    if let Some(color) = str_to_color(color) {
-        color
+    if let Some(color) = resolve_color(color) {
+        color::downgrade(color, color::capability())
     } else {
         error!("Did not recognize the color '{}'", color);
         Color::White
     }
 }
 
-fn str_to_color(color: &str) -> Option<Color> {
-    use Color::*;
-
-    let c = color.to_lowercase();
-    Some(match &c[..] {
-        // TUI colors
-        "black" => Black,
-        "red" => Red,
-        "green" => Green,
-        "yellow" => Yellow,
-        "blue" => Blue,
-        "magenta" => Magenta,
-        "cyan" => Cyan,
-        "gray" => Gray,
-        "dark gray" => DarkGray,
-        "light red" => LightRed,
-        "light green" => LightGreen,
-        "light yellow" => LightYellow,
-        "light blue" => LightBlue,
-        "light magenta" => LightMagenta,
-        "light cyan" => LightCyan,
-        "white" => White,
-
-        // Custom colors
-        "orange" => Rgb(255, 127, 0),
-
-        // Hex and unknown
-        c => {
-            if !c.starts_with('#') || c.len() != 7 {
-                return None;
-            }
-
-            let r = &c[1..3];
-            let g = &c[3..5];
-            let b = &c[5..7];
-
-            let r = u8::from_str_radix(r, 16).ok()?;
-            let g = u8::from_str_radix(g, 16).ok()?;
-            let b = u8::from_str_radix(b, 16).ok()?;
-
-            Rgb(r, g, b)
-        }
-    })
+/// Look up a color by its name within the palette
+fn palette_to_color(name: &str, palette: &Palette) -> Option<Color> {
+    palette
+        .0
+        .get(name.trim())
+        .and_then(|color| parse_color(color))
 }
 
 fn get_modifier(modifier: &str) -> Option<Modifier> {
@@ -91,7 +81,7 @@ fn get_modifier(modifier: &str) -> Option<Modifier> {
         "underlined" => Modifier::UNDERLINED,
         "slow blink" => Modifier::SLOW_BLINK,
         "rapid blink" => Modifier::RAPID_BLINK,
-        "reversed" => Modifier::REVERSED,
+        "reverse" | "reversed" => Modifier::REVERSED,
         "crossed out" => Modifier::CROSSED_OUT,
         "hidden" => Modifier::HIDDEN,
         _ => return None,
@@ -224,17 +214,61 @@ toml_config_struct! { Config, PartialConfig, RoughConfig,
 
     focus_behaviour => FocusBehaviour,
 
+    console => ConsoleConfig [PartialConsoleConfig, RoughConsoleConfig],
+    theme => ThemeConfig [PartialThemeConfig, RoughThemeConfig],
     background => BackgroundConfig [PartialBackgroundConfig, RoughBackgroundConfig],
+    layout => LayoutConfig [PartialLayoutConfig, RoughLayoutConfig],
+    banner => BannerConfig [PartialBannerConfig, RoughBannerConfig],
+    info_panel => InfoPanelConfig [PartialInfoPanelConfig, RoughInfoPanelConfig],
+    screensaver => ScreensaverConfig [PartialScreensaverConfig, RoughScreensaverConfig],
 
     power_controls => PowerControlConfig [PartialPowerControlConfig, RoughPowerControlConfig],
     environment_switcher => SwitcherConfig [PartialSwitcherConfig, RoughSwitcherConfig],
+    locale_switcher => LocaleSwitcherConfig [PartialLocaleSwitcherConfig, RoughLocaleSwitcherConfig],
+    messages => MessagesConfig [PartialMessagesConfig, RoughMessagesConfig],
+    status_message => StatusMessageConfig [PartialStatusMessageConfig, RoughStatusMessageConfig],
     username_field => UsernameFieldConfig [PartialUsernameFieldConfig, RoughUsernameFieldConfig],
     password_field => PasswordFieldConfig [PartialPasswordFieldConfig, RoughPasswordFieldConfig],
 
     x11 => X11Config [PartialX11Config, RoughX11Config],
     wayland => WaylandConfig [PartialWaylandConfig, RoughWaylandConfig],
+
+    user_sessions => UserSessionsConfig [PartialUserSessionsConfig, RoughUserSessionsConfig],
+    custom_environments => CustomEnvironmentsConfig [PartialCustomEnvironmentsConfig, RoughCustomEnvironmentsConfig],
+    xwayland_kiosk => XwaylandKioskConfig [PartialXwaylandKioskConfig, RoughXwaylandKioskConfig],
+
+    session_shutdown => SessionShutdownConfig [PartialSessionShutdownConfig, RoughSessionShutdownConfig],
+    session_env => SessionEnvConfig [PartialSessionEnvConfig, RoughSessionEnvConfig],
 }
 
+toml_config_struct! { ConsoleConfig, PartialConsoleConfig, RoughConsoleConfig,
+    font => String,
+    fonts_path => String,
+
+    auto_size => bool,
+    min_columns => u16,
+    min_rows => u16,
+}
+
+toml_config_struct! { ThemeConfig, PartialThemeConfig, RoughThemeConfig,
+    name => String,
+    themes_path => String,
+    palette => Palette [Palette, RoughPalette],
+
+    program_console_palette => bool,
+    console_palette => Vec<String>,
+}
+
+/// Colors by name that can be used in place of a color
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(transparent)]
+#[repr(transparent)]
+pub struct Palette(pub HashMap<String, String>);
+#[derive(Clone, Debug, Deserialize)]
+#[serde(transparent)]
+#[repr(transparent)]
+struct RoughPalette(pub HashMap<String, PossibleVariable<String>>);
+
 toml_config_struct! { BackgroundStyleConfig, PartialBackgroundStyleConfig, RoughBackgroundStyleConfig,
     color => String,
     show_border => bool,
@@ -246,6 +280,64 @@ toml_config_struct! { BackgroundConfig, PartialBackgroundConfig, RoughBackground
     style => BackgroundStyleConfig [PartialBackgroundStyleConfig, RoughBackgroundStyleConfig],
 }
 
+toml_config_struct! { LayoutConfig, PartialLayoutConfig, RoughLayoutConfig,
+    alignment => LayoutAlignment,
+
+    vertical_centering => bool,
+    horizontal_centering => bool,
+
+    widget_order => Vec<String>,
+    spacing => u16,
+
+    width => u16,
+    height => u16,
+
+    show_border => bool,
+    border_color => String,
+}
+
+toml_config_struct! { BannerConfig, PartialBannerConfig, RoughBannerConfig,
+    show_banner => bool,
+
+    text => String,
+    path => String,
+
+    alignment => TextAlignment,
+
+    color => String,
+    modifiers => String,
+    line_colors => Vec<String>,
+}
+
+toml_config_struct! { InfoPanelConfig, PartialInfoPanelConfig, RoughInfoPanelConfig,
+    show_info_panel => bool,
+
+    format => String,
+    time_format => String,
+
+    show_issue => bool,
+    issue_path => String,
+
+    refresh_interval_secs => u16,
+
+    alignment => TextAlignment,
+
+    color => String,
+    modifiers => String,
+}
+
+toml_config_struct! { ScreensaverConfig, PartialScreensaverConfig, RoughScreensaverConfig,
+    idle_timeout_secs => u16,
+
+    mode => ScreensaverMode,
+    move_interval_secs => u16,
+    time_format => String,
+    color => String,
+
+    blank_console => bool,
+    clear_password => bool,
+}
+
 toml_config_struct! { PowerControlConfig, PartialPowerControlConfig, RoughPowerControlConfig,
     hint_margin => u16,
     base_entries => PowerControlVec [PartialPowerControlVec, RoughPowerControlVec],
@@ -333,6 +425,27 @@ toml_config_struct! { SwitcherConfig, PartialSwitcherConfig, RoughSwitcherConfig
     no_envs_modifiers_focused => String,
 }
 
+toml_config_struct! { LocaleSwitcherConfig, PartialLocaleSwitcherConfig, RoughLocaleSwitcherConfig,
+    enabled => bool,
+
+    locales => Vec<String>,
+    locales_path => String,
+
+    remember => bool,
+
+    max_display_length => u16,
+    no_locales_text => String,
+}
+
+toml_config_struct! { MessagesConfig, PartialMessagesConfig, RoughMessagesConfig,
+    language => String,
+    catalogs_path => String,
+}
+
+toml_config_struct! { StatusMessageConfig, PartialStatusMessageConfig, RoughStatusMessageConfig,
+    error_timeout_secs => u16,
+}
+
 toml_config_struct! { InputFieldStyle, PartialInputFieldStyle, RoughInputFieldStyle,
     show_title => bool,
     title => String,
@@ -370,7 +483,11 @@ toml_config_struct! { X11Config, PartialX11Config, RoughX11Config,
     xserver_log_path => String,
 
     xserver_path => String,
-    xauth_path => String,
+    xserver_args => Vec<String>,
+    xserver_rootless => bool,
+
+    xauthority_in_runtime_dir => bool,
+    xserver_auth_dir => String,
 
     scripts_path => String,
     xsetup_path => String,
@@ -380,6 +497,197 @@ toml_config_struct! { X11Config, PartialX11Config, RoughX11Config,
 toml_config_struct! { WaylandConfig, PartialWaylandConfig, RoughWaylandConfig,
     scripts_path => String,
     wayland_sessions_path => String,
+
+    readiness => WaylandReadiness,
+    startup_timeout_secs => u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum LayoutAlignment {
+    #[serde(rename = "top-left")]
+    TopLeft,
+    #[serde(rename = "center")]
+    Center,
+    #[serde(rename = "left")]
+    Left,
+    #[serde(rename = "right")]
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum TextAlignment {
+    #[serde(rename = "left")]
+    Left,
+    #[serde(rename = "center")]
+    Center,
+    #[serde(rename = "right")]
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum ScreensaverMode {
+    #[serde(rename = "blank")]
+    Blank,
+    #[serde(rename = "clock")]
+    Clock,
+    #[serde(rename = "banner")]
+    Banner,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum WaylandReadiness {
+    #[serde(rename = "none")]
+    None,
+    #[serde(rename = "socket")]
+    Socket,
+}
+
+toml_config_struct! { UserSessionsConfig, PartialUserSessionsConfig, RoughUserSessionsConfig,
+    enabled => bool,
+    path => String,
+}
+
+toml_config_struct! { CustomEnvironmentsConfig, PartialCustomEnvironmentsConfig, RoughCustomEnvironmentsConfig,
+    entries => CustomEnvironmentVec [PartialCustomEnvironmentVec, RoughCustomEnvironmentVec],
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(transparent)]
+#[repr(transparent)]
+pub struct CustomEnvironmentVec(pub Vec<CustomEnvironment>);
+#[derive(Clone, Deserialize)]
+#[serde(transparent)]
+#[repr(transparent)]
+pub struct PartialCustomEnvironmentVec(pub Vec<PartialCustomEnvironment>);
+#[derive(Clone, Debug, Deserialize)]
+#[serde(transparent)]
+#[repr(transparent)]
+struct RoughCustomEnvironmentVec(pub Vec<RoughCustomEnvironment>);
+
+toml_config_struct! { CustomEnvironment, PartialCustomEnvironment, RoughCustomEnvironment,
+    name => String,
+    cmd => String,
+    args => Vec<String>,
+    session_type => SessionType,
+    working_dir => String,
+    env => Vec<String>,
+}
+
+impl Default for CustomEnvironment {
+    fn default() -> Self {
+        CustomEnvironment {
+            name: "".to_string(),
+            cmd: "".to_string(),
+            args: Vec::new(),
+            session_type: SessionType::Tty,
+            working_dir: "".to_string(),
+            env: Vec::new(),
+        }
+    }
+}
+
+toml_config_struct! { SessionShutdownConfig, PartialSessionShutdownConfig, RoughSessionShutdownConfig,
+    terminate_timeout_secs => u16,
+    kill_remaining_processes => bool,
+}
+
+toml_config_struct! { SessionEnvConfig, PartialSessionEnvConfig, RoughSessionEnvConfig,
+    read_etc_environment => bool,
+    read_login_defs => bool,
+
+    set => SessionEnvList,
+    unset => SessionEnvList,
+    append_path => SessionEnvList,
+
+    overrides => SessionEnvOverrideVec [PartialSessionEnvOverrideVec, RoughSessionEnvOverrideVec],
+}
+
+/// A list of strings that may contain `$VAR` references to the session environment
+///
+/// These are expanded when the session is started and not with the configuration variables.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(transparent)]
+#[repr(transparent)]
+pub struct SessionEnvList(pub Vec<String>);
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(transparent)]
+#[repr(transparent)]
+pub struct SessionEnvOverrideVec(pub Vec<SessionEnvOverride>);
+
+#[derive(Clone, Deserialize)]
+#[serde(transparent)]
+#[repr(transparent)]
+pub struct PartialSessionEnvOverrideVec(pub Vec<PartialSessionEnvOverride>);
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(transparent)]
+#[repr(transparent)]
+struct RoughSessionEnvOverrideVec(pub Vec<RoughSessionEnvOverride>);
+
+toml_config_struct! { SessionEnvOverride, PartialSessionEnvOverride, RoughSessionEnvOverride,
+    environment => String,
+
+    set => SessionEnvList,
+    unset => SessionEnvList,
+    append_path => SessionEnvList,
+}
+
+impl Default for SessionEnvOverride {
+    fn default() -> Self {
+        SessionEnvOverride {
+            environment: "".to_string(),
+            set: SessionEnvList::default(),
+            unset: SessionEnvList::default(),
+            append_path: SessionEnvList::default(),
+        }
+    }
+}
+
+toml_config_struct! { XwaylandKioskConfig, PartialXwaylandKioskConfig, RoughXwaylandKioskConfig,
+    xwayland_path => String,
+    entries => XwaylandKioskVec [PartialXwaylandKioskVec, RoughXwaylandKioskVec],
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(transparent)]
+#[repr(transparent)]
+pub struct XwaylandKioskVec(pub Vec<XwaylandKiosk>);
+#[derive(Clone, Deserialize)]
+#[serde(transparent)]
+#[repr(transparent)]
+pub struct PartialXwaylandKioskVec(pub Vec<PartialXwaylandKiosk>);
+#[derive(Clone, Debug, Deserialize)]
+#[serde(transparent)]
+#[repr(transparent)]
+struct RoughXwaylandKioskVec(pub Vec<RoughXwaylandKiosk>);
+
+toml_config_struct! { XwaylandKiosk, PartialXwaylandKiosk, RoughXwaylandKiosk,
+    name => String,
+    compositor => String,
+    client => String,
+}
+
+impl Default for XwaylandKiosk {
+    fn default() -> Self {
+        XwaylandKiosk {
+            name: "".to_string(),
+            compositor: "".to_string(),
+            client: "".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum SessionType {
+    #[serde(rename = "tty")]
+    Tty,
+    #[serde(rename = "x11")]
+    X11,
+    #[serde(rename = "wayland")]
+    Wayland,
+    #[serde(rename = "mir")]
+    Mir,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -437,6 +745,192 @@ impl<'de> Deserialize<'de> for SwitcherVisibility {
     }
 }
 
+/// A color or modifier within the configuration that is not recognized
+#[derive(Debug)]
+pub enum StyleError {
+    UnknownColor { field: String, color: String },
+    UnknownModifier { field: String, modifier: String },
+}
+
+impl std::error::Error for StyleError {}
+
+impl Display for StyleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StyleError::UnknownColor { field, color } => {
+                write!(f, "Unknown color '{color}' for '{field}'")
+            }
+            StyleError::UnknownModifier { field, modifier } => {
+                write!(f, "Unknown modifier '{modifier}' for '{field}'")
+            }
+        }
+    }
+}
+
+/// The fields with colors and modifiers, together with their path within the configuration
+#[derive(Default)]
+struct StyleFields<'a> {
+    colors: Vec<(String, &'a str)>,
+    modifiers: Vec<(String, &'a str)>,
+}
+
+impl<'a> StyleFields<'a> {
+    fn color(&mut self, field: impl Into<String>, color: &'a str) {
+        self.colors.push((field.into(), color));
+    }
+
+    fn modifiers(&mut self, field: impl Into<String>, modifiers: &'a str) {
+        self.modifiers.push((field.into(), modifiers));
+    }
+
+    fn power_controls(&mut self, prefix: &str, power_controls: &'a PowerControlVec) {
+        for (index, power_control) in power_controls.0.iter().enumerate() {
+            self.color(
+                format!("{prefix}[{index}].hint_color"),
+                &power_control.hint_color,
+            );
+            self.modifiers(
+                format!("{prefix}[{index}].hint_modifiers"),
+                &power_control.hint_modifiers,
+            );
+        }
+    }
+
+    fn switcher(&mut self, prefix: &str, switcher: &'a SwitcherConfig) {
+        for (name, color) in [
+            ("toggle_hint_color", &switcher.toggle_hint_color),
+            ("mover_color", &switcher.mover_color),
+            ("mover_color_focused", &switcher.mover_color_focused),
+            ("selected_color", &switcher.selected_color),
+            ("selected_color_focused", &switcher.selected_color_focused),
+            ("neighbour_color", &switcher.neighbour_color),
+            ("neighbour_color_focused", &switcher.neighbour_color_focused),
+            ("no_envs_color", &switcher.no_envs_color),
+            ("no_envs_color_focused", &switcher.no_envs_color_focused),
+        ] {
+            self.color(format!("{prefix}.{name}"), color);
+        }
+
+        for (name, modifiers) in [
+            ("toggle_hint_modifiers", &switcher.toggle_hint_modifiers),
+            ("mover_modifiers", &switcher.mover_modifiers),
+            ("mover_modifiers_focused", &switcher.mover_modifiers_focused),
+            ("selected_modifiers", &switcher.selected_modifiers),
+            (
+                "selected_modifiers_focused",
+                &switcher.selected_modifiers_focused,
+            ),
+            ("neighbour_modifiers", &switcher.neighbour_modifiers),
+            (
+                "neighbour_modifiers_focused",
+                &switcher.neighbour_modifiers_focused,
+            ),
+            ("no_envs_modifiers", &switcher.no_envs_modifiers),
+            (
+                "no_envs_modifiers_focused",
+                &switcher.no_envs_modifiers_focused,
+            ),
+        ] {
+            self.modifiers(format!("{prefix}.{name}"), modifiers);
+        }
+    }
+
+    fn input_field(&mut self, prefix: &str, style: &'a InputFieldStyle) {
+        for (name, color) in [
+            ("title_color", &style.title_color),
+            ("title_color_focused", &style.title_color_focused),
+            ("content_color", &style.content_color),
+            ("content_color_focused", &style.content_color_focused),
+            ("border_color", &style.border_color),
+            ("border_color_focused", &style.border_color_focused),
+        ] {
+            self.color(format!("{prefix}.{name}"), color);
+        }
+    }
+}
+
+impl Config {
+    fn style_fields(&self) -> StyleFields<'_> {
+        let mut fields = StyleFields::default();
+
+        fields.color("background.style.color", &self.background.style.color);
+        fields.color(
+            "background.style.border_color",
+            &self.background.style.border_color,
+        );
+        fields.color("layout.border_color", &self.layout.border_color);
+        for (index, color) in self.theme.console_palette.iter().enumerate() {
+            fields.color(format!("theme.console_palette[{index}]"), color);
+        }
+
+        fields.color("banner.color", &self.banner.color);
+        fields.modifiers("banner.modifiers", &self.banner.modifiers);
+        for (index, color) in self.banner.line_colors.iter().enumerate() {
+            fields.color(format!("banner.line_colors[{index}]"), color);
+        }
+
+        fields.color("info_panel.color", &self.info_panel.color);
+        fields.modifiers("info_panel.modifiers", &self.info_panel.modifiers);
+        fields.color("screensaver.color", &self.screensaver.color);
+
+        fields.power_controls(
+            "power_controls.base_entries",
+            &self.power_controls.base_entries,
+        );
+        fields.power_controls("power_controls.entries", &self.power_controls.entries);
+        fields.switcher("environment_switcher", &self.environment_switcher);
+        fields.input_field("username_field.style", &self.username_field.style);
+        fields.input_field("password_field.style", &self.password_field.style);
+
+        fields
+    }
+
+    /// Check that all colors and modifiers within the configuration are recognized
+    ///
+    /// Colors may refer to the palette of the theme, but the palette itself can only contain actual
+    /// colors.
+    pub fn validate_styles(&self) -> Result<(), StyleError> {
+        let palette = &self.theme.palette;
+
+        for (name, color) in &palette.0 {
+            if parse_color(color).is_none() {
+                return Err(StyleError::UnknownColor {
+                    field: format!("theme.palette.{name}"),
+                    color: color.clone(),
+                });
+            }
+        }
+
+        let fields = self.style_fields();
+
+        for (field, color) in fields.colors {
+            if parse_color(color)
+                .or_else(|| palette_to_color(color, palette))
+                .is_none()
+            {
+                return Err(StyleError::UnknownColor {
+                    field,
+                    color: color.to_string(),
+                });
+            }
+        }
+
+        for (field, modifiers) in fields.modifiers {
+            if let Some(modifier) = modifiers
+                .split(',')
+                .find(|modifier| !modifier.trim().is_empty() && get_modifier(modifier).is_none())
+            {
+                return Err(StyleError::UnknownModifier {
+                    field,
+                    modifier: modifier.trim().to_string(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
 impl Default for Config {
     fn default() -> Config {
         toml::from_str(include_str!("../extra/config.toml")).unwrap_or_else(|e| {
@@ -457,12 +951,55 @@ impl PartialConfig {
 
         file.read_to_string(&mut contents)?;
 
+        Self::from_contents(&contents, variables)
+    }
+
+    pub fn from_contents(
+        contents: &str,
+        variables: Option<&Variables>,
+    ) -> Result<PartialConfig, Box<dyn std::error::Error>> {
+        warn_deprecated_keys(contents);
+
+        match variables {
+            Some(variables) => {
+                let rough = toml::from_str::<RoughConfig>(contents)?;
+                Ok(rough.into_partial(variables)?)
+            }
+            None => Ok(toml::from_str::<PartialConfig>(contents)?),
+        }
+    }
+
+    /// Load a partial configuration from an already parsed table
+    pub fn from_table(
+        table: toml::value::Table,
+        variables: Option<&Variables>,
+    ) -> Result<PartialConfig, Box<dyn std::error::Error>> {
+        let value = Value::Table(table);
+
         match variables {
             Some(variables) => {
-                let rough = toml::from_str::<RoughConfig>(&contents)?;
+                let rough = value.try_into::<RoughConfig>()?;
                 Ok(rough.into_partial(variables)?)
             }
-            None => Ok(toml::from_str::<PartialConfig>(&contents)?),
+            None => Ok(value.try_into::<PartialConfig>()?),
+        }
+    }
+}
+
+/// Warn about settings within `contents` that are ignored, because they are no longer used
+fn warn_deprecated_keys(contents: &str) {
+    let Ok(table) = toml::from_str::<toml::value::Table>(contents) else {
+        return;
+    };
+
+    for (section, key, reason) in DEPRECATED_KEYS {
+        let is_set = table
+            .get(section)
+            .and_then(Value::as_table)
+            .is_some_and(|section| section.contains_key(key));
+
+        if is_set {
+            warn!("Ignoring the deprecated setting `{section}.{key}`. {reason}.");
         }
     }
 }
@@ -559,6 +1096,28 @@ impl Display for VariableInsertionError {
     }
 }
 
+impl Palette {
+    /// Colors of the partial palette are added or replace the colors with the same name
+    pub fn merge_in_partial(&mut self, partial: Palette) {
+        self.0.extend(partial.0);
+    }
+}
+
+impl RoughPalette {
+    pub fn into_partial(self, variables: &Variables) -> Result<Palette, VariableInsertionError> {
+        self.0
+            .into_iter()
+            .map(|(name, color)| {
+                Ok((
+                    name,
+                    <String as VariableInsertable>::insert(color, variables)?,
+                ))
+            })
+            .collect::<Result<HashMap<String, String>, VariableInsertionError>>()
+            .map(Palette)
+    }
+}
+
 impl PowerControlVec {
     pub fn merge_in_partial(&mut self, partial: PartialPowerControlVec) {
         *self = PowerControlVec(
@@ -575,6 +1134,93 @@ impl PowerControlVec {
     }
 }
 
+impl CustomEnvironmentVec {
+    pub fn merge_in_partial(&mut self, partial: PartialCustomEnvironmentVec) {
+        *self = CustomEnvironmentVec(
+            partial
+                .0
+                .into_iter()
+                .map(|partial_elem| {
+                    let mut elem = CustomEnvironment::default();
+                    elem.merge_in_partial(partial_elem);
+                    elem
+                })
+                .collect::<Vec<CustomEnvironment>>(),
+        );
+    }
+}
+
+impl RoughCustomEnvironmentVec {
+    pub fn into_partial(
+        self,
+        variables: &Variables,
+    ) -> Result<PartialCustomEnvironmentVec, VariableInsertionError> {
+        self.0
+            .into_iter()
+            .map(|rough_elem| rough_elem.into_partial(variables))
+            .collect::<Result<Vec<PartialCustomEnvironment>, VariableInsertionError>>()
+            .map(PartialCustomEnvironmentVec)
+    }
+}
+
+impl XwaylandKioskVec {
+    pub fn merge_in_partial(&mut self, partial: PartialXwaylandKioskVec) {
+        *self = XwaylandKioskVec(
+            partial
+                .0
+                .into_iter()
+                .map(|partial_elem| {
+                    let mut elem = XwaylandKiosk::default();
+                    elem.merge_in_partial(partial_elem);
+                    elem
+                })
+                .collect::<Vec<XwaylandKiosk>>(),
+        );
+    }
+}
+
+impl RoughXwaylandKioskVec {
+    pub fn into_partial(
+        self,
+        variables: &Variables,
+    ) -> Result<PartialXwaylandKioskVec, VariableInsertionError> {
+        self.0
+            .into_iter()
+            .map(|rough_elem| rough_elem.into_partial(variables))
+            .collect::<Result<Vec<PartialXwaylandKiosk>, VariableInsertionError>>()
+            .map(PartialXwaylandKioskVec)
+    }
+}
+
+impl SessionEnvOverrideVec {
+    pub fn merge_in_partial(&mut self, partial: PartialSessionEnvOverrideVec) {
+        *self = SessionEnvOverrideVec(
+            partial
+                .0
+                .into_iter()
+                .map(|partial_elem| {
+                    let mut elem = SessionEnvOverride::default();
+                    elem.merge_in_partial(partial_elem);
+                    elem
+                })
+                .collect::<Vec<SessionEnvOverride>>(),
+        );
+    }
+}
+
+impl RoughSessionEnvOverrideVec {
+    pub fn into_partial(
+        self,
+        variables: &Variables,
+    ) -> Result<PartialSessionEnvOverrideVec, VariableInsertionError> {
+        self.0
+            .into_iter()
+            .map(|rough_elem| rough_elem.into_partial(variables))
+            .collect::<Result<Vec<PartialSessionEnvOverride>, VariableInsertionError>>()
+            .map(PartialSessionEnvOverrideVec)
+    }
+}
+
 impl RoughPowerControlVec {
     pub fn into_partial(
         self,
@@ -658,6 +1304,11 @@ non_string_var_insert! {
     ShellLoginFlag ["shell login flag"],
     FocusBehaviour ["focus behavior"],
     SwitcherVisibility ["switcher visibility"],
+    SessionType ["session type"],
+    WaylandReadiness ["wayland readiness"],
+    LayoutAlignment ["layout alignment"],
+    TextAlignment ["text alignment"],
+    ScreensaverMode ["screensaver mode"],
 }
 
 impl VariableInsertable for String {
@@ -703,26 +1354,102 @@ impl VariableInsertable for String {
     }
 }
 
-/// Iterator over variables in a given string
-/// Assumes the presence of quotes
-struct VariableIterator<'a> {
+impl VariableInsertable for SessionEnvList {
+    fn insert_with_depth(
+        value: PossibleVariable<Self>,
+        _: &Variables,
+        _: u32,
+    ) -> Result<Self, VariableInsertionError> {
+        // The `$VAR` references are left for the session environment
+        match value {
+            PossibleVariable::Value(list) => Ok(list),
+            PossibleVariable::Variable(_) => Err(VariableInsertionError::InvalidType {
+                expected: "array",
+                gotten: "string",
+            }),
+        }
+    }
+}
+
+impl VariableInsertable for Vec<String> {
+    fn insert_with_depth(
+        value: PossibleVariable<Self>,
+        variables: &Variables,
+        depth: u32,
+    ) -> Result<Self, VariableInsertionError> {
+        use VariableInsertionError as E;
+
+        if depth == Self::DEPTH_LIMIT {
+            return Err(E::DepthLimitReached);
+        }
+
+        match value {
+            PossibleVariable::Variable(s) => {
+                // Ignore surrounding spaces
+                let s = s.trim();
+
+                let var = VariableIterator::new(s).next().ok_or(E::InvalidType {
+                    expected: "array",
+                    gotten: "string",
+                })?;
+
+                // Not whole string is variable
+                if var.span() != (0..s.len()) {
+                    return Err(E::ImpossibleVariableCast {
+                        var_ident: var.ident().to_string(),
+                        expected_type: "array",
+                    });
+                }
+
+                let value = <PossibleVariable<Vec<String>>>::try_from(
+                    variables
+                        .0
+                        .get(var.ident())
+                        .ok_or(E::UnsetVariable {
+                            var_ident: var.ident().to_string(),
+                        })?
+                        .clone(),
+                )
+                .map_err(|_| E::UnexpectedVariableType {
+                    var_ident: var.ident().to_string(),
+                    expected_type: "array",
+                })?;
+
+                Self::insert_with_depth(value, variables, depth + 1)
+            }
+            PossibleVariable::Value(items) => items
+                .into_iter()
+                .map(|item| {
+                    String::insert_with_depth(PossibleVariable::Value(item), variables, depth)
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Iterator over the `$var` and `${var}` references in a given string
+///
+/// A `$` that is not followed by a name gives a variable with an empty identifier.
+pub(crate) struct VariableIterator<'a> {
     inner: &'a str,
     offset: usize,
 }
 
-struct Variable<'a> {
+pub(crate) struct Variable<'a> {
     start: usize,
     ident: &'a str,
+    braced: bool,
 }
 
 impl<'a> Variable<'a> {
     const START_SYMBOL: &'static str = "$";
 
-    fn span(&self) -> std::ops::Range<usize> {
-        self.start..self.start + Self::START_SYMBOL.len() + self.ident.len()
+    pub(crate) fn span(&self) -> std::ops::Range<usize> {
+        let braces_len = if self.braced { 2 } else { 0 };
+        self.start..self.start + Self::START_SYMBOL.len() + self.ident.len() + braces_len
     }
 
-    fn ident(&self) -> &str {
+    pub(crate) fn ident(&self) -> &str {
         self.ident
     }
 }
@@ -738,6 +1465,8 @@ impl<'a> VariableIterator<'a> {
 impl<'a> Iterator for VariableIterator<'a> {
     type Item = Variable<'a>;
     fn next(&mut self) -> Option<Self::Item> {
+        let is_ident_char = |c: char| c.is_alphanumeric() || c == '_';
+
         let s = &self.inner[self.offset..];
 
         let start = match s.find(Variable::START_SYMBOL) {
@@ -747,24 +1476,45 @@ impl<'a> Iterator for VariableIterator<'a> {
 
         // skip the "$ pattern
         let s = &s[start + Variable::START_SYMBOL.len()..];
-
-        // Find the first not variable token.
-        let end = s
-            .find(|c: char| !c.is_alphanumeric() && c != '_')
-            .unwrap_or(s.len());
-
         let start = self.offset + start;
-        self.offset = start + Variable::START_SYMBOL.len() + end;
 
-        let ident = &s[..end];
+        // A `${var}` reference
+        let braced_ident = s.strip_prefix('{').and_then(|s| {
+            let end = s.find('}')?;
+            let ident = &s[..end];
+            (!ident.is_empty() && ident.chars().all(is_ident_char)).then_some(ident)
+        });
+
+        let variable = match braced_ident {
+            Some(ident) => Variable {
+                start,
+                ident,
+                braced: true,
+            },
+            None => {
+                // Find the first not variable token.
+                let end = s.find(|c: char| !is_ident_char(c)).unwrap_or(s.len());
+
+                Variable {
+                    start,
+                    ident: &s[..end],
+                    braced: false,
+                }
+            }
+        };
+
+        self.offset = variable.span().end;
 
-        Some(Variable { start, ident })
+        Some(variable)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::VariableIterator;
+    use super::{
+        Config, PartialConfig, PossibleVariable, StyleError, VariableInsertable, VariableIterator,
+        Variables,
+    };
 
     #[test]
     fn test_variable_iterator() {
@@ -794,5 +1544,77 @@ mod tests {
         assert_var_iter!("$a()$b", ("a", "b"));
         assert_var_iter!("$0    $1", ("0", "1"));
         assert_var_iter!("$var1    $var2    $var3  ", ("var1", "var2", "var3"));
+        assert_var_iter!("${a}b$c", ("a", "c"));
+        assert_var_iter!("${}${a", ("", ""));
+    }
+
+    #[test]
+    fn test_string_list_insertion() {
+        let variables: Variables = toml::from_str(
+            r#"
+            session = "main"
+            args = ["new-session", "-s", "$session"]
+            "#,
+        )
+        .unwrap();
+
+        let insert = |value: PossibleVariable<Vec<String>>| {
+            <Vec<String> as VariableInsertable>::insert(value, &variables).unwrap()
+        };
+
+        assert_eq!(
+            insert(PossibleVariable::Value(vec![
+                "-s".to_string(),
+                "$session".to_string()
+            ])),
+            ["-s", "main"]
+        );
+        assert_eq!(
+            insert(PossibleVariable::Variable("$args".to_string())),
+            ["new-session", "-s", "main"]
+        );
+    }
+
+    #[test]
+    fn style_validation() {
+        let mut config = Config::default();
+        assert!(config.validate_styles().is_ok());
+
+        config.username_field.style.title_color = "accent".to_string();
+        assert!(matches!(
+            config.validate_styles(),
+            Err(StyleError::UnknownColor { field, .. }) if field == "username_field.style.title_color"
+        ));
+
+        config
+            .theme
+            .palette
+            .0
+            .insert("accent".to_string(), "rgb(255, 127, 0)".to_string());
+        assert!(config.validate_styles().is_ok());
+
+        config.banner.modifiers = "bold, blinking".to_string();
+        assert!(matches!(
+            config.validate_styles(),
+            Err(StyleError::UnknownModifier { field, modifier })
+                if field == "banner.modifiers" && modifier == "blinking"
+        ));
+    }
+
+    #[test]
+    fn deprecated_keys() {
+        let partial = PartialConfig::from_contents(
+            r#"
+            [x11]
+            xauth_path = "/usr/bin/xauth"
+            xserver_path = "/usr/bin/Xorg"
+            "#,
+            None,
+        )
+        .unwrap();
+
+        let mut config = Config::default();
+        config.merge_in_partial(partial);
+        assert_eq!(config.x11.xserver_path, "/usr/bin/Xorg");
     }
 }
@@ -0,0 +1,532 @@
+//! This module implements the loading of fonts onto the Linux console.
+//!
+//! Fonts are read from PSF1 or PSF2 files, which are usually gzipped within
+//! `/usr/share/consolefonts`. The unicode table of the font is loaded together with its glyphs, so
+//! that characters map onto the right glyphs.
+
+use std::error::Error;
+use std::fs::{self, read_to_string};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use flate2::read::GzDecoder;
+use log::{info, warn};
+
+use crate::chvt::{
+    get_console_font, get_unicode_map, is_a_console, set_console_font, set_unicode_map, ChvtError,
+    ConsoleFontData, UniPair, FONT_GLYPH_ROWS,
+};
+use crate::config::ConsoleConfig;
+
+const PSF1_MAGIC: [u8; 2] = [0x36, 0x04];
+const PSF1_MODE512: u8 = 0x01;
+const PSF1_MODEHASTAB: u8 = 0x02;
+const PSF1_MODESEQ: u8 = 0x04;
+const PSF1_SEPARATOR: u16 = 0xFFFF;
+const PSF1_STARTSEQ: u16 = 0xFFFE;
+
+const PSF2_MAGIC: [u8; 4] = [0x72, 0xB5, 0x4A, 0x86];
+const PSF2_HAS_UNICODE_TABLE: u32 = 0x01;
+const PSF2_SEPARATOR: u8 = 0xFF;
+const PSF2_STARTSEQ: u8 = 0xFE;
+
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+
+/// The largest font file that is read, before or after decompression. The largest fonts of the
+/// console are 64x128 pixels with 512 glyphs, which takes 512 KiB together with its unicode table.
+const MAX_FONT_SIZE: u64 = 1024 * 1024;
+
+/// The extensions that are tried when looking up a font by name
+const FONT_EXTENSIONS: [&str; 5] = [".psfu.gz", ".psf.gz", ".psfu", ".psf", ""];
+
+/// Where the kernel exposes the resolution of the framebuffer
+const FRAMEBUFFER_SIZE_PATH: &str = "/sys/class/graphics/fb0/virtual_size";
+
+/// A font that can be loaded onto the console
+#[derive(Debug, Clone)]
+struct Font {
+    glyphs: ConsoleFontData,
+    unicode_map: Vec<UniPair>,
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let bytes = bytes.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Put `charcount` glyphs of `width` x `height` pixels into the layout of the kernel
+fn console_font_data(
+    data: &[u8],
+    charcount: usize,
+    width: usize,
+    height: usize,
+) -> ConsoleFontData {
+    let row_size = (width + 7) / 8;
+
+    // The kernel only accepts fonts with 256 or 512 glyphs
+    let padded_charcount = if charcount <= 256 { 256 } else { 512 };
+
+    let mut padded = vec![0u8; padded_charcount * FONT_GLYPH_ROWS * row_size];
+    for (glyph, padded_glyph) in data
+        .chunks(height * row_size)
+        .take(charcount)
+        .zip(padded.chunks_mut(FONT_GLYPH_ROWS * row_size))
+    {
+        padded_glyph[..glyph.len()].copy_from_slice(glyph);
+    }
+
+    ConsoleFontData {
+        width: width as u32,
+        height: height as u32,
+        charcount: padded_charcount as u32,
+        data: padded,
+    }
+}
+
+fn parse_psf1(bytes: &[u8]) -> Result<Font, Box<dyn Error>> {
+    let mode = bytes[2];
+    let height = usize::from(bytes[3]);
+    let charcount = if mode & PSF1_MODE512 != 0 { 512 } else { 256 };
+
+    if height == 0 || height > FONT_GLYPH_ROWS {
+        return Err(format!("Glyphs of 8x{height} are not supported by the console").into());
+    }
+
+    let glyphs_end = 4 + charcount * height;
+    let data = bytes.get(4..glyphs_end).ok_or("Font is truncated")?;
+
+    let mut unicode_map = Vec::new();
+    if mode & (PSF1_MODEHASTAB | PSF1_MODESEQ) != 0 {
+        let mut fontpos = 0;
+        let mut in_sequence = false;
+
+        for entry in bytes[glyphs_end..].chunks_exact(2) {
+            match u16::from_le_bytes([entry[0], entry[1]]) {
+                PSF1_SEPARATOR => {
+                    fontpos += 1;
+                    in_sequence = false;
+                }
+                // Sequences of multiple code points cannot be mapped onto a single glyph
+                PSF1_STARTSEQ => in_sequence = true,
+                unicode if !in_sequence => unicode_map.push(UniPair { unicode, fontpos }),
+                _ => {}
+            }
+        }
+    }
+
+    Ok(Font {
+        glyphs: console_font_data(data, charcount, 8, height),
+        unicode_map,
+    })
+}
+
+fn parse_psf2(bytes: &[u8]) -> Result<Font, Box<dyn Error>> {
+    let header = |offset| read_u32(bytes, offset).ok_or("Font header is truncated");
+
+    let header_size = header(8)? as usize;
+    let flags = header(12)?;
+    let charcount = header(16)? as usize;
+    let glyph_size = header(20)? as usize;
+    let height = header(24)? as usize;
+    let width = header(28)? as usize;
+
+    let row_size = (width + 7) / 8;
+    if width == 0 || width > 32 || height == 0 || height > FONT_GLYPH_ROWS {
+        return Err(format!("Glyphs of {width}x{height} are not supported by the console").into());
+    }
+    if charcount > 512 {
+        return Err(
+            format!("Fonts with {charcount} glyphs are not supported by the console").into(),
+        );
+    }
+    if glyph_size != row_size * height {
+        return Err("Font has an invalid glyph size".into());
+    }
+
+    let glyphs_end = header_size + charcount * glyph_size;
+    let data = bytes
+        .get(header_size..glyphs_end)
+        .ok_or("Font is truncated")?;
+
+    let mut unicode_map = Vec::new();
+    if flags & PSF2_HAS_UNICODE_TABLE != 0 {
+        let table = bytes[glyphs_end..].split(|byte| *byte == PSF2_SEPARATOR);
+
+        for (fontpos, entry) in table.take(charcount).enumerate() {
+            // Sequences of multiple code points cannot be mapped onto a single glyph
+            let singles = entry.split(|byte| *byte == PSF2_STARTSEQ).next();
+            let singles = String::from_utf8_lossy(singles.unwrap_or_default());
+
+            unicode_map.extend(singles.chars().filter_map(|c| {
+                Some(UniPair {
+                    unicode: u16::try_from(u32::from(c)).ok()?,
+                    fontpos: fontpos as u16,
+                })
+            }));
+        }
+    }
+
+    Ok(Font {
+        glyphs: console_font_data(data, charcount, width, height),
+        unicode_map,
+    })
+}
+
+/// Read at most `MAX_FONT_SIZE` bytes from `reader`
+fn read_font_bytes(reader: impl Read) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut bytes = Vec::new();
+    reader.take(MAX_FONT_SIZE + 1).read_to_end(&mut bytes)?;
+
+    if bytes.len() as u64 > MAX_FONT_SIZE {
+        return Err("Font is too large".into());
+    }
+
+    Ok(bytes)
+}
+
+/// Parse a, possibly gzipped, PSF1 or PSF2 font
+///
+/// Fonts are decompressed at most once, so the decompressed data is parsed as is.
+fn parse_font(bytes: &[u8]) -> Result<Font, Box<dyn Error>> {
+    if bytes.starts_with(&GZIP_MAGIC) {
+        parse_psf(&read_font_bytes(GzDecoder::new(bytes))?)
+    } else {
+        parse_psf(bytes)
+    }
+}
+
+/// Parse an uncompressed PSF1 or PSF2 font
+fn parse_psf(bytes: &[u8]) -> Result<Font, Box<dyn Error>> {
+    if bytes.starts_with(&PSF1_MAGIC) && bytes.len() >= 4 {
+        parse_psf1(bytes)
+    } else if bytes.starts_with(&PSF2_MAGIC) {
+        parse_psf2(bytes)
+    } else {
+        Err("Not a PSF font".into())
+    }
+}
+
+/// Find the file of the font with `name`
+fn font_path(fonts_path: &str, name: &str) -> Option<PathBuf> {
+    if name.contains('/') {
+        return Some(PathBuf::from(name));
+    }
+
+    FONT_EXTENSIONS
+        .iter()
+        .map(|extension| Path::new(fonts_path).join(format!("{name}{extension}")))
+        .find(|path| path.is_file())
+}
+
+/// Split a font name into the parts before and after its size, e.g. `ter-v32n` into `ter-v` and
+/// `n`. The size is the last number within the name.
+fn font_family(name: &str) -> Option<(&str, &str)> {
+    let end = name.rfind(|c: char| c.is_ascii_digit())? + 1;
+    let start = name[..end]
+        .rfind(|c: char| !c.is_ascii_digit())
+        .map_or(0, |start| start + 1);
+
+    Some((&name[..start], &name[end..]))
+}
+
+/// The sizes of the fonts within `fonts_path` that belong to the same family as `name`
+fn family_sizes(fonts_path: &str, name: &str) -> Vec<String> {
+    let Some((prefix, suffix)) = font_family(name) else {
+        return Vec::new();
+    };
+
+    let Ok(entries) = fs::read_dir(fonts_path) else {
+        return Vec::new();
+    };
+
+    let mut sizes: Vec<String> = entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter_map(|file_name| {
+            let stem = FONT_EXTENSIONS
+                .iter()
+                .find_map(|extension| file_name.strip_suffix(extension))?;
+            let size = stem.strip_prefix(prefix)?.strip_suffix(suffix)?;
+
+            (!size.is_empty() && size.chars().all(|c| c.is_ascii_digit())).then(|| size.to_string())
+        })
+        .collect();
+
+    // Largest sizes first
+    sizes.sort_by_key(|size| std::cmp::Reverse(size.parse::<u32>().unwrap_or_default()));
+    sizes.dedup();
+    sizes
+}
+
+/// Get the width and height of the framebuffer in pixels
+fn framebuffer_size() -> Option<(u32, u32)> {
+    let size = read_to_string(FRAMEBUFFER_SIZE_PATH).ok()?;
+    let (width, height) = size.trim().split_once(',')?;
+
+    Some((width.parse().ok()?, height.parse().ok()?))
+}
+
+fn load_font_file(config: &ConsoleConfig, name: &str) -> Result<Font, Box<dyn Error>> {
+    let path = font_path(&config.fonts_path, name)
+        .ok_or_else(|| format!("Font '{name}' does not exist"))?;
+
+    parse_font(&read_font_bytes(fs::File::open(path)?)?)
+}
+
+/// Load the configured font, or the largest font of its family that fits on the screen
+fn load_font(config: &ConsoleConfig) -> Result<Font, Box<dyn Error>> {
+    let name = config.font.trim();
+
+    if !config.auto_size || name.contains('/') {
+        return load_font_file(config, name);
+    }
+
+    let Some((screen_width, screen_height)) = framebuffer_size() else {
+        warn!("Failed to get the resolution of the framebuffer. Using the configured font size.");
+        return load_font_file(config, name);
+    };
+
+    let (prefix, suffix) = font_family(name).unwrap_or((name, ""));
+    let fits = |font: &Font| {
+        screen_width / font.glyphs.width >= u32::from(config.min_columns)
+            && screen_height / font.glyphs.height >= u32::from(config.min_rows)
+    };
+
+    let mut smallest = None;
+    for size in family_sizes(&config.fonts_path, name) {
+        let name = format!("{prefix}{size}{suffix}");
+        match load_font_file(config, &name) {
+            Ok(font) if fits(&font) => {
+                info!("Selected console font '{name}' for a {screen_width}x{screen_height} screen");
+                return Ok(font);
+            }
+            Ok(font) => smallest = Some(font),
+            Err(err) => warn!("Failed to load console font '{name}'. Reason: {err}"),
+        }
+    }
+
+    // Use the smallest size when none of the sizes fit
+    match smallest {
+        Some(font) => Ok(font),
+        None => load_font_file(config, name),
+    }
+}
+
+/// The font the console had before Lemurs loaded the configured font
+pub struct ConsoleFont {
+    original: Font,
+    loaded: Font,
+}
+
+impl ConsoleFont {
+    /// Load the configured font onto the console
+    ///
+    /// Nothing is done when no font is configured or Lemurs does not run on the Linux console.
+    pub fn load(config: &ConsoleConfig) -> Option<Self> {
+        if config.font.trim().is_empty() || !is_a_console(libc::STDOUT_FILENO) {
+            return None;
+        }
+
+        let loaded = match load_font(config) {
+            Ok(font) => font,
+            Err(err) => {
+                warn!("Failed to load the console font. Reason: {err}");
+                return None;
+            }
+        };
+
+        let original = get_console_font(libc::STDOUT_FILENO).and_then(|glyphs| {
+            let unicode_map = get_unicode_map(libc::STDOUT_FILENO)?;
+            Ok(Font {
+                glyphs,
+                unicode_map,
+            })
+        });
+        let original = match original {
+            Ok(original) => original,
+            Err(err) => {
+                warn!("Failed to get the font of the console. Reason: {err}");
+                return None;
+            }
+        };
+
+        info!("Loading the console font '{}'", config.font.trim());
+        let font = Self { original, loaded };
+        font.apply();
+
+        Some(font)
+    }
+
+    fn set(font: &Font) -> Result<(), ChvtError> {
+        set_console_font(libc::STDOUT_FILENO, &font.glyphs)?;
+
+        // Fonts without a unicode table keep the mapping of the previous font
+        if font.unicode_map.is_empty() {
+            return Ok(());
+        }
+
+        set_unicode_map(libc::STDOUT_FILENO, &font.unicode_map)
+    }
+
+    /// Show the configured font
+    pub fn apply(&self) {
+        if let Err(err) = Self::set(&self.loaded) {
+            warn!("Failed to set the font of the console. Reason: {err}");
+        }
+    }
+
+    /// Show the font the console had before the configured font was loaded
+    ///
+    /// This has to happen while the console is still in text mode, so before a session takes over
+    /// the console.
+    pub fn restore(&self) -> Result<(), ChvtError> {
+        Self::set(&self.original)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    /// PSF1 font with 256 glyphs of 8x2, where glyph 65 is mapped onto 'A' and 'Ä'
+    fn psf1_font() -> Vec<u8> {
+        let mut psf1 = vec![0x36, 0x04, PSF1_MODEHASTAB, 2];
+        psf1.extend((0..=255u8).flat_map(|glyph| [glyph, !glyph]));
+        for glyph in 0..256u16 {
+            if glyph == 65 {
+                psf1.extend(0x41u16.to_le_bytes());
+                psf1.extend(0xC4u16.to_le_bytes());
+            }
+            psf1.extend(PSF1_SEPARATOR.to_le_bytes());
+        }
+        psf1
+    }
+
+    /// PSF2 font with 2 glyphs of 12x3 and a sequence that is skipped
+    fn psf2_font() -> Vec<u8> {
+        let mut psf2 = PSF2_MAGIC.to_vec();
+        for field in [0u32, 32, PSF2_HAS_UNICODE_TABLE, 2, 6, 3, 12] {
+            psf2.extend(field.to_le_bytes());
+        }
+        psf2.extend(1..=12u8);
+        psf2.extend("a".bytes().chain([PSF2_SEPARATOR]));
+        psf2.extend("é".bytes().chain([PSF2_STARTSEQ]).chain("e\u{301}".bytes()));
+        psf2.push(PSF2_SEPARATOR);
+        psf2
+    }
+
+    #[test]
+    fn psf1_glyphs_are_padded_to_the_glyph_rows() {
+        let font = parse_font(&psf1_font()).unwrap();
+
+        assert_eq!(
+            (font.glyphs.width, font.glyphs.height, font.glyphs.charcount),
+            (8, 2, 256)
+        );
+        assert_eq!(font.glyphs.data.len(), 256 * FONT_GLYPH_ROWS);
+        assert_eq!(font.glyphs.data[65 * FONT_GLYPH_ROWS..][..3], [65, !65, 0]);
+    }
+
+    #[test]
+    fn psf1_unicode_table_maps_characters_onto_glyphs() {
+        let font = parse_font(&psf1_font()).unwrap();
+
+        assert_eq!(
+            font.unicode_map,
+            [
+                UniPair {
+                    unicode: 0x41,
+                    fontpos: 65
+                },
+                UniPair {
+                    unicode: 0xC4,
+                    fontpos: 65
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn psf2_glyph_rows_are_two_bytes_wide() {
+        let font = parse_font(&psf2_font()).unwrap();
+
+        // The kernel only accepts fonts with 256 or 512 glyphs
+        assert_eq!(
+            (font.glyphs.width, font.glyphs.height, font.glyphs.charcount),
+            (12, 3, 256)
+        );
+        assert_eq!(
+            font.glyphs.data[2 * FONT_GLYPH_ROWS..][..7],
+            [7, 8, 9, 10, 11, 12, 0]
+        );
+    }
+
+    #[test]
+    fn psf2_unicode_sequences_are_skipped() {
+        let font = parse_font(&psf2_font()).unwrap();
+
+        assert_eq!(
+            font.unicode_map,
+            [
+                UniPair {
+                    unicode: 'a' as u16,
+                    fontpos: 0
+                },
+                UniPair {
+                    unicode: 'é' as u16,
+                    fontpos: 1
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn gzipped_font_is_decompressed() {
+        let font = parse_font(&gzip(&psf2_font())).unwrap();
+        assert_eq!(font.glyphs.width, 12);
+    }
+
+    #[test]
+    fn truncated_font_is_rejected() {
+        assert!(parse_font(&psf2_font()[..40]).is_err());
+    }
+
+    #[test]
+    fn unknown_font_format_is_rejected() {
+        assert!(parse_font(b"not a font").is_err());
+    }
+
+    fn gzip(bytes: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(bytes).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn doubly_gzipped_font_is_rejected() {
+        let mut psf1 = vec![0x36, 0x04, 0, 1];
+        psf1.extend([0; 256]);
+
+        assert!(parse_font(&gzip(&psf1)).is_ok());
+        assert!(parse_font(&gzip(&gzip(&psf1))).is_err());
+    }
+
+    #[test]
+    fn oversized_gzipped_font_is_rejected() {
+        let mut psf1 = vec![0x36, 0x04, 0, 1];
+        psf1.resize(MAX_FONT_SIZE as usize, 0);
+        assert!(parse_font(&gzip(&psf1)).is_ok());
+
+        psf1.push(0);
+        assert!(parse_font(&gzip(&psf1)).is_err());
+    }
+
+    #[test]
+    fn font_families() {
+        assert_eq!(font_family("ter-v32n"), Some(("ter-v", "n")));
+        assert_eq!(font_family("Lat2-Terminus16"), Some(("Lat2-Terminus", "")));
+        assert_eq!(font_family("default"), None);
+    }
+}
@@ -1,64 +1,60 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::env;
+use std::process::Command;
 
-use log::{error, info};
+use log::info;
 
-/// The `EnvironmentContainer` is abstract the process environment and allows for restoring to an
-/// earlier state
-#[derive(Debug)]
+/// The `EnvironmentContainer` holds the environment and working directory of a session
+///
+/// The environment of Lemurs itself is never changed. Instead, the container is applied to every
+/// command that is spawned for the session with [`EnvironmentContainer::apply`].
+#[derive(Debug, Clone, Default)]
 pub struct EnvironmentContainer {
-    snapshot: HashMap<String, String>,
-    snapshot_pwd: String,
-    owned: HashMap<&'static str, String>,
+    variables: BTreeMap<String, String>,
+    working_dir: Option<String>,
 }
 
 impl EnvironmentContainer {
-    pub fn take_snapshot() -> Self {
-        let pwd = match env::current_dir().map(|pathbuf| pathbuf.to_str().map(str::to_string)) {
-            Ok(Some(s)) => s,
-            Ok(None) | Err(_) => {
-                error!("Could not find the working directory when taking snapshot");
-                String::from("/")
-            }
-        };
-
+    /// Create a container starting from the environment Lemurs was started with
+    pub fn from_process_env() -> Self {
         Self {
-            snapshot: env::vars().collect::<HashMap<String, String>>(),
-            owned: HashMap::default(),
-            snapshot_pwd: pwd,
+            variables: env::vars().collect(),
+            working_dir: None,
         }
     }
 
-    /// Set an environment variable and own the value
-    pub fn set(&mut self, key: &'static str, value: impl Into<String>) {
+    /// Get the value of an environment variable
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.variables.get(key).map(String::as_str)
+    }
+
+    /// Set an environment variable
+    pub fn set(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        let key = key.into();
         let value = value.into();
 
-        env::set_var(key, &value);
         info!("Set environment variable '{}' to '{}'", key, value);
 
-        self.owned.insert(key, value);
+        self.variables.insert(key, value);
     }
 
     /// Set an environment variable if it is not already set
-    ///
-    /// If the variable was already set, then the [`EnvironmentContainer`] considers the value as
-    /// one of its own.
-    pub fn set_or_own(&mut self, key: &'static str, value: impl Into<String>) {
-        if let Ok(value) = env::var(key) {
+    pub fn set_if_unset(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        let key = key.into();
+
+        if let Some(value) = self.variables.get(&key) {
             info!(
                 "Skipped setting environment variable '{}'. It was already set to '{}'",
                 key, value
             );
-            self.owned.insert(key, value);
         } else {
             self.set(key, value)
         }
     }
 
-    pub fn remove_var(&mut self, key: &'static str) {
-        if env::var(key).is_ok() {
+    pub fn remove_var(&mut self, key: &str) {
+        if self.variables.remove(key).is_some() {
             info!("Preemptively removed environment variable '{key}'",);
-            env::remove_var(key);
         }
     }
 
@@ -66,39 +62,20 @@ impl EnvironmentContainer {
     pub fn set_current_dir(&mut self, value: impl Into<String>) {
         let value = value.into();
 
-        if env::set_current_dir(&value).is_ok() {
-            info!("Successfully changed working directory to {}!", value);
-        } else {
-            error!("Failed to change the working directory to {}", value);
-        }
-        self.snapshot_pwd = value;
+        info!("Set working directory to {}", value);
+
+        self.variables.insert("PWD".to_string(), value.clone());
+        self.working_dir = Some(value);
     }
-}
 
-impl Drop for EnvironmentContainer {
-    fn drop(&mut self) {
-        // Remove all owned variables for which we have an accurate environment value
-        info!("Removing session environment variables");
-        for (key, value) in self.owned.iter() {
-            if env::var(key).as_ref() == Ok(value) {
-                env::remove_var(key);
-            }
-        }
+    /// Make `command` run with exactly this environment and working directory
+    pub fn apply<'a>(&self, command: &'a mut Command) -> &'a mut Command {
+        command.env_clear().envs(&self.variables);
 
-        // Restore all snapshot values for which disappeared
-        info!("Reverting to environment before session");
-        for (key, value) in self.snapshot.iter() {
-            if env::var(key).is_err() {
-                env::set_var(key, value);
-            }
+        if let Some(working_dir) = &self.working_dir {
+            command.current_dir(working_dir);
         }
 
-        info!("Reverting to environment before session");
-        if env::set_current_dir(&self.snapshot_pwd).is_err() {
-            error!(
-                "Failed to change the working directory back to {}",
-                &self.snapshot_pwd
-            );
-        }
+        command
     }
 }
@@ -2,18 +2,23 @@ use log::{info, warn};
 use std::fs::{read_to_string, write};
 
 use crate::config::Config;
+use crate::locale::verify_locale;
 
 const USERNAME_LENGTH_LIMIT: usize = 32;
+const USER_LOCALES_LIMIT: usize = 64;
 
 // Saved in the /var/cache/lemurs file as
 // ```
 // ENVIRONMENT\n
-// USERNAME
+// USERNAME\n
+// USERNAME=LOCALE\n
+// ...
 // ```
 #[derive(Debug, Clone)]
 pub struct CachedInfo {
     environment: Option<String>,
     username: Option<String>,
+    user_locales: Vec<(String, String)>,
 }
 
 fn verify_username(username: &str) -> bool {
@@ -47,6 +52,36 @@ impl CachedInfo {
     pub fn username(&self) -> Option<&str> {
         self.username.as_deref()
     }
+
+    pub fn locale(&self, username: &str) -> Option<&str> {
+        self.user_locales
+            .iter()
+            .find(|(user, _)| user == username)
+            .map(|(_, locale)| locale.as_str())
+    }
+}
+
+/// Parse the `USERNAME=LOCALE` lines of the cache file and skip the invalid ones
+fn parse_user_locales<'a>(lines: impl Iterator<Item = &'a str>) -> Vec<(String, String)> {
+    lines
+        .filter_map(|line| {
+            let (username, locale) = line.split_once('=')?;
+
+            if !verify_username(username) || !verify_locale(locale) {
+                warn!("Cached locale line is invalid and is therefore not loaded.");
+                return None;
+            }
+
+            Some((username.to_string(), locale.to_string()))
+        })
+        .take(USER_LOCALES_LIMIT)
+        .collect()
+}
+
+fn read_user_locales(cache_path: &str) -> Vec<(String, String)> {
+    read_to_string(cache_path)
+        .map(|cached| parse_user_locales(cached.lines().skip(2)))
+        .unwrap_or_default()
 }
 
 pub fn get_cached_information(config: &Config) -> CachedInfo {
@@ -56,8 +91,8 @@ pub fn get_cached_information(config: &Config) -> CachedInfo {
 
     match read_to_string(cache_path) {
         Ok(cached) => {
-            // Remove any line feeds
-            let cached = cached.trim().to_string();
+            // Remove any trailing line feeds. Leading empty lines are an empty environment.
+            let cached = cached.trim_end().to_string();
 
             let mut lines = cached.lines();
 
@@ -90,6 +125,7 @@ pub fn get_cached_information(config: &Config) -> CachedInfo {
             CachedInfo {
                 environment: cached_environment.map(|x| x.to_string()),
                 username: cached_username.map(|x| x.to_string()),
+                user_locales: parse_user_locales(lines),
             }
         }
         Err(err) => {
@@ -97,12 +133,22 @@ pub fn get_cached_information(config: &Config) -> CachedInfo {
             CachedInfo {
                 environment: None,
                 username: None,
+                user_locales: Vec::new(),
             }
         }
     }
 }
 
-pub fn set_cache(environment: Option<&str>, username: Option<&str>, config: &Config) {
+/// Write the cache file
+///
+/// The `user_locale` is a pair of username and locale. It is stored next to the locales that are
+/// already cached for other users.
+pub fn set_cache(
+    environment: Option<&str>,
+    username: Option<&str>,
+    user_locale: Option<(&str, &str)>,
+    config: &Config,
+) {
     let cache_path = &config.cache_path;
 
     info!("Attempting to set cache: {cache_path}");
@@ -125,11 +171,25 @@ pub fn set_cache(environment: Option<&str>, username: Option<&str>, config: &Con
         None
     };
 
-    let cache_file_content = format!(
+    let mut user_locales = read_user_locales(cache_path);
+    if let Some((locale_username, locale)) = user_locale {
+        if verify_username(locale_username) && verify_locale(locale) {
+            user_locales.retain(|(user, _)| user != locale_username);
+            user_locales.insert(0, (locale_username.to_string(), locale.to_string()));
+            user_locales.truncate(USER_LOCALES_LIMIT);
+        } else {
+            warn!("Username or locale is not valid and therefore the locale is not cached.");
+        }
+    }
+
+    let mut cache_file_content = format!(
         "{}\n{}\n",
         environment.unwrap_or_default(),
         username.unwrap_or_default()
     );
+    for (user, locale) in user_locales {
+        cache_file_content.push_str(&format!("{user}={locale}\n"));
+    }
 
     match write(cache_path, cache_file_content) {
         Err(err) => {
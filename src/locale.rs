@@ -0,0 +1,242 @@
+use log::{info, warn};
+use std::fs::{read_dir, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::config::LocaleSwitcherConfig;
+
+const LOCALE_LENGTH_LIMIT: usize = 64;
+
+/// The file in which `localedef` stores compiled locales
+const LOCALE_ARCHIVE_FILE: &str = "locale-archive";
+/// The magic number at the start of a locale archive
+const LOCALE_ARCHIVE_MAGIC: u32 = 0xde020109;
+
+/// Whether `locale` looks like a locale name (e.g. `en_US.UTF-8` or `sr_RS@latin`)
+pub fn verify_locale(locale: &str) -> bool {
+    // REGEX: "^[a-zA-Z0-9_.@-]+$"
+
+    !locale.is_empty()
+        && locale.len() <= LOCALE_LENGTH_LIMIT
+        && locale.bytes().all(
+            |b| matches!(b, b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'_' | b'.' | b'@' | b'-'),
+        )
+}
+
+/// Read from `offset` within `archive` until `buf` is full or the end of `archive` is reached
+///
+/// Returns the number of bytes that were read.
+fn read_at<R: Read + Seek>(archive: &mut R, offset: u64, buf: &mut [u8]) -> Option<usize> {
+    archive.seek(SeekFrom::Start(offset)).ok()?;
+
+    let mut filled = 0;
+    while filled < buf.len() {
+        match archive.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(_) => return None,
+        }
+    }
+
+    Some(filled)
+}
+
+fn read_u32<R: Read + Seek>(archive: &mut R, offset: u64) -> Option<u32> {
+    let mut bytes = [0; 4];
+    (read_at(archive, offset, &mut bytes)? == bytes.len()).then(|| u32::from_ne_bytes(bytes))
+}
+
+/// Get the names of the locales stored in a glibc locale archive
+///
+/// The archive starts with a header that points to a hash table of names. Every used entry of this
+/// table holds the offset of a nul-terminated locale name. Only the header, the table and the names
+/// are read, since the archive also contains the data of all locales.
+fn locale_archive_names<R: Read + Seek>(archive: &mut R) -> Vec<String> {
+    const HEADER_NAMEHASH_OFFSET: u64 = 8;
+    const HEADER_NAMEHASH_SIZE: u64 = 16;
+    const NAMEHASH_ENTRY_SIZE: usize = 12;
+    /// Far more entries than there are locales
+    const MAX_NAMEHASH_SIZE: usize = 1 << 16;
+
+    if read_u32(archive, 0) != Some(LOCALE_ARCHIVE_MAGIC) {
+        warn!("Locale archive has an invalid header");
+        return Vec::new();
+    }
+
+    let (Some(namehash_offset), Some(namehash_size), Ok(archive_size)) = (
+        read_u32(archive, HEADER_NAMEHASH_OFFSET),
+        read_u32(archive, HEADER_NAMEHASH_SIZE),
+        archive.seek(SeekFrom::End(0)),
+    ) else {
+        return Vec::new();
+    };
+
+    // A corrupt archive can claim a table that is far larger than the archive itself
+    let namehash_size = (namehash_size as usize)
+        .min(MAX_NAMEHASH_SIZE)
+        .min(usize::try_from(archive_size).unwrap_or(usize::MAX) / NAMEHASH_ENTRY_SIZE);
+
+    let mut namehash = vec![0; namehash_size * NAMEHASH_ENTRY_SIZE];
+    let Some(namehash_len) = read_at(archive, namehash_offset.into(), &mut namehash) else {
+        return Vec::new();
+    };
+    namehash.truncate(namehash_len);
+
+    namehash
+        .chunks_exact(NAMEHASH_ENTRY_SIZE)
+        .filter_map(|entry| {
+            // An offset of 0 marks an unused entry
+            let name_offset = u32::from_ne_bytes([entry[4], entry[5], entry[6], entry[7]]);
+            if name_offset == 0 {
+                return None;
+            }
+
+            // Longer names are not valid locales, so there is no need to look further for the nul
+            let mut name = [0; LOCALE_LENGTH_LIMIT + 1];
+            let name_len = read_at(archive, name_offset.into(), &mut name)?;
+            let name = &name[..name_len];
+            let name = &name[..name.iter().position(|b| *b == 0)?];
+
+            std::str::from_utf8(name).ok().map(str::to_string)
+        })
+        .collect()
+}
+
+/// Get the locales that are installed in `locales_path`
+///
+/// This is the equivalent of `locale -a`. It includes the locales within the locale archive and
+/// the locales that are stored as separate directories.
+fn installed_locales(locales_path: &Path) -> Vec<String> {
+    let mut locales = Vec::new();
+
+    match File::open(locales_path.join(LOCALE_ARCHIVE_FILE)) {
+        Ok(mut archive) => locales.extend(locale_archive_names(&mut archive)),
+        Err(err) => info!("No locale archive loaded. Reason: {err}"),
+    }
+
+    match read_dir(locales_path) {
+        Ok(entries) => {
+            locales.extend(entries.filter_map(|entry| {
+                let entry = entry.ok()?;
+
+                // Every compiled locale has at least a character classification
+                if !entry.path().join("LC_CTYPE").is_file() {
+                    return None;
+                }
+
+                entry.file_name().into_string().ok()
+            }));
+        }
+        Err(err) => warn!(
+            "Failed to read locales directory '{}'. Reason: {err}",
+            locales_path.display()
+        ),
+    }
+
+    locales
+}
+
+/// Get the locales that can be selected in the locale switcher
+pub fn get_locales(config: &LocaleSwitcherConfig) -> Vec<String> {
+    let mut locales = if config.locales.is_empty() {
+        installed_locales(Path::new(&config.locales_path))
+    } else {
+        config.locales.clone()
+    };
+
+    locales.retain(|locale| {
+        let is_valid = verify_locale(locale);
+        if !is_valid {
+            warn!("Skipping invalid locale '{locale}'");
+        }
+        is_valid
+    });
+
+    if config.locales.is_empty() {
+        locales.sort();
+        locales.dedup();
+    }
+
+    info!("Found {} locale(s)", locales.len());
+
+    locales
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// A locale archive with a name hash table of 3 entries, one of which is unused
+    fn locale_archive() -> Vec<u8> {
+        fn push_u32(data: &mut Vec<u8>, value: u32) {
+            data.extend_from_slice(&value.to_ne_bytes());
+        }
+
+        let mut data = Vec::new();
+
+        // Header with a name hash table of 3 entries directly after it
+        push_u32(&mut data, LOCALE_ARCHIVE_MAGIC);
+        push_u32(&mut data, 0);
+        push_u32(&mut data, 20);
+        push_u32(&mut data, 2);
+        push_u32(&mut data, 3);
+
+        let names_offset = 20 + 3 * 12;
+        for name_offset in [names_offset, 0, names_offset + 12] {
+            push_u32(&mut data, 0);
+            push_u32(&mut data, name_offset);
+            push_u32(&mut data, 0);
+        }
+
+        data.extend_from_slice(b"en_US.utf8\0\0");
+        data.extend_from_slice(b"nl_NL.utf8\0");
+
+        data
+    }
+
+    fn archive_names(data: &[u8]) -> Vec<String> {
+        locale_archive_names(&mut Cursor::new(data))
+    }
+
+    #[test]
+    fn locale_archive_lists_used_names() {
+        assert_eq!(
+            archive_names(&locale_archive()),
+            vec!["en_US.utf8".to_string(), "nl_NL.utf8".to_string()]
+        );
+    }
+
+    #[test]
+    fn truncated_locale_archive_has_no_names() {
+        assert!(archive_names(&locale_archive()[..30]).is_empty());
+    }
+
+    #[test]
+    fn corrupt_table_size_is_bounded_by_archive_size() {
+        let mut data = locale_archive();
+        data[16..20].copy_from_slice(&u32::MAX.to_ne_bytes());
+        assert_eq!(
+            archive_names(&data),
+            vec!["en_US.utf8".to_string(), "nl_NL.utf8".to_string()]
+        );
+    }
+
+    #[test]
+    fn locale_archive_with_bad_magic_has_no_names() {
+        let mut data = locale_archive();
+        data[0] = 0;
+        assert!(archive_names(&data).is_empty());
+    }
+
+    #[test]
+    fn locale_names() {
+        assert!(verify_locale("en_US.UTF-8"));
+        assert!(verify_locale("sr_RS@latin"));
+        assert!(verify_locale("C"));
+        assert!(!verify_locale(""));
+        assert!(!verify_locale("en_US UTF-8"));
+        assert!(!verify_locale("../etc"));
+    }
+}
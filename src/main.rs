@@ -13,10 +13,14 @@ use ratatui::Terminal;
 mod auth;
 mod chvt;
 mod cli;
+mod color;
 mod config;
+mod console_font;
 mod env_container;
 mod info_caching;
+mod locale;
 mod post_login;
+mod theme;
 mod ui;
 
 use auth::try_auth;
@@ -32,9 +36,10 @@ use self::{
     auth::AuthenticationError,
     env_container::EnvironmentContainer,
     post_login::env_variables::{
-        remove_xdg, set_basic_variables, set_display, set_seat_vars, set_session_params,
-        set_session_vars, set_xdg_common_paths,
+        remove_xdg, set_basic_variables, set_display, set_etc_environment, set_locale,
+        set_seat_vars, set_session_env, set_session_params, set_session_vars, set_xdg_common_paths,
     },
+    post_login::x::resolve_display,
 };
 
 const DEFAULT_VARIABLES_PATH: &str = "/etc/lemurs/variables.toml";
@@ -82,13 +87,36 @@ fn merge_in_configuration(config: &mut Config, cli: &Cli) {
         .as_deref()
         .unwrap_or_else(|| Path::new(DEFAULT_CONFIG_PATH));
 
-    match config::PartialConfig::from_file(load_config_path, variables.as_ref()) {
-        Ok(partial_config) => {
+    // A configuration with unknown colors or modifiers is treated as a configuration that cannot
+    // be loaded
+    let loaded_config = config::PartialConfig::from_file(load_config_path, variables.as_ref())
+        .and_then(|partial_config| {
+            let merge_over = |mut loaded_config: Config| {
+                loaded_config.merge_in_partial(partial_config.clone());
+                loaded_config
+            };
+
+            // The theme goes between the defaults and the configuration file, so the file can
+            // adjust the theme. Which theme to use is only known once the file is merged in.
+            let mut loaded_config = merge_over(config.clone());
+            if let Some(theme) = theme::load_theme(&loaded_config.theme, variables.as_ref()) {
+                let mut themed_config = config.clone();
+                themed_config.merge_in_partial(theme);
+                loaded_config = merge_over(themed_config);
+            }
+
+            loaded_config.validate_styles()?;
+
+            Ok(loaded_config)
+        });
+
+    match loaded_config {
+        Ok(loaded_config) => {
             info!(
                 "Successfully loaded configuration file from '{}'",
                 load_config_path.display()
             );
-            config.merge_in_partial(partial_config)
+            *config = loaded_config;
         }
         Err(err) => {
             // If we have given it a specific config path, it should crash if this file cannot be
@@ -109,6 +137,8 @@ fn merge_in_configuration(config: &mut Config, cli: &Cli) {
         }
     }
 
+    config::set_palette(config.theme.palette.clone());
+
     if let Some(xsessions) = cli.xsessions.as_ref() {
         config.x11.xsessions_path = xsessions.display().to_string();
     }
@@ -216,6 +246,9 @@ fn main() -> Result<(), Box<dyn Error>> {
             config.tty = tty;
         }
 
+        // Processes orphaned by sessions are reparented to Lemurs, so that they can be reaped
+        post_login::shutdown::become_subreaper();
+
         // Switch to the proper tty
         info!("Switching to tty {}", config.tty);
 
@@ -262,7 +295,7 @@ pub fn tui_disable(mut terminal: Terminal<CrosstermBackend<io::Stdout>>) -> io::
 struct Hooks<'a> {
     pre_validate: Option<&'a dyn Fn()>,
     pre_auth: Option<&'a dyn Fn()>,
-    pre_environment: Option<&'a dyn Fn()>,
+    pre_environment: Option<&'a dyn Fn() -> Result<(), EnvironmentStartError>>,
     pre_wait: Option<&'a dyn Fn()>,
     pre_return: Option<&'a dyn Fn()>,
 }
@@ -287,7 +320,9 @@ impl From<AuthenticationError> for StartSessionError {
 fn start_session(
     username: &str,
     password: &str,
+    environment_name: &str,
     post_login_env: &PostLoginEnvironment,
+    locale: Option<&str>,
     hooks: &Hooks<'_>,
     config: &Config,
 ) -> Result<(), StartSessionError> {
@@ -300,40 +335,66 @@ fn start_session(
         pre_validate_hook();
     }
 
-    let mut process_env = EnvironmentContainer::take_snapshot();
+    let mut process_env = EnvironmentContainer::from_process_env();
 
     if let Some(pre_auth_hook) = hooks.pre_auth {
         pre_auth_hook();
     }
 
-    if matches!(post_login_env, PostLoginEnvironment::X { .. }) {
-        set_display(&config.x11.x11_display, &mut process_env);
+    // An automatically selected display stays reserved until the environment is spawned, by which
+    // time the X server accepts connections
+    let mut display_reservation = None;
+    if post_login_env.uses_x_server() {
+        let (display, reservation) =
+            resolve_display(&config.x11.x11_display).map_err(EnvironmentStartError::XSetup)?;
+        set_display(&display, &mut process_env);
+        display_reservation = reservation;
     }
     set_session_params(&mut process_env, post_login_env);
     remove_xdg(&mut process_env);
 
-    let auth_session = try_auth(username, password, &config.pam_service)?;
+    // The `PATH` of the session comes from `/etc/environment`, PAM or `login.defs` instead of
+    // the `PATH` of Lemurs itself
+    process_env.remove_var("PATH");
+
+    let tty = config.tty;
+
+    // The seat is handed to PAM, so it can register the session on it
+    set_seat_vars(&mut process_env, tty);
+
+    set_etc_environment(&mut process_env, &config.session_env);
+
+    let auth_session = try_auth(username, password, &config.pam_service, &mut process_env)?;
 
     if let Some(pre_environment_hook) = hooks.pre_environment {
-        pre_environment_hook();
+        pre_environment_hook()?;
     }
 
-    let tty = config.tty;
     let uid = auth_session.uid;
     let homedir = &auth_session.home_dir;
     let shell = &auth_session.shell;
 
-    set_seat_vars(&mut process_env, tty);
     set_session_vars(&mut process_env, uid);
-    set_basic_variables(&mut process_env, username, homedir, shell);
+    set_basic_variables(
+        &mut process_env,
+        username,
+        homedir,
+        shell,
+        uid,
+        &config.session_env,
+    );
     set_xdg_common_paths(&mut process_env, homedir);
+    set_session_env(&mut process_env, &config.session_env, environment_name);
+    if let Some(locale) = locale {
+        set_locale(&mut process_env, locale);
+    }
 
     let spawned_environment = post_login_env.spawn(&auth_session, &mut process_env, config)?;
+    drop(display_reservation);
 
     let pid = spawned_environment.pid();
 
     let utmpx_session = add_utmpx_entry(username, tty, pid);
-    drop(process_env);
 
     info!("Waiting for environment to terminate");
 
@@ -341,7 +402,7 @@ fn start_session(
         pre_wait_hook();
     }
 
-    spawned_environment.wait();
+    spawned_environment.wait(&config.session_shutdown);
 
     info!("Environment terminated. Returning to Lemurs...");
 
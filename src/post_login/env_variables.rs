@@ -1,5 +1,8 @@
-use log::info;
+use std::fs;
 
+use log::{info, warn};
+
+use crate::config::{SessionEnvConfig, SessionEnvList, VariableIterator};
 use crate::env_container::EnvironmentContainer;
 
 use super::PostLoginEnvironment;
@@ -48,8 +51,8 @@ pub fn set_session_params(
 pub fn set_seat_vars(process_env: &mut EnvironmentContainer, tty: u8) {
     info!("Setting XDG Seat Variables");
 
-    process_env.set_or_own("XDG_SEAT", "seat0");
-    process_env.set_or_own("XDG_VTNR", &tty.to_string());
+    process_env.set_if_unset("XDG_SEAT", "seat0");
+    process_env.set_if_unset("XDG_VTNR", &tty.to_string());
 }
 
 // NOTE: This uid: u32 might be better set to libc::uid_t
@@ -57,8 +60,78 @@ pub fn set_seat_vars(process_env: &mut EnvironmentContainer, tty: u8) {
 pub fn set_session_vars(process_env: &mut EnvironmentContainer, uid: u32) {
     info!("Setting XDG Session Variables");
 
-    process_env.set_or_own("XDG_RUNTIME_DIR", &format!("/run/user/{uid}"));
-    process_env.set_or_own("XDG_SESSION_ID", "1");
+    process_env.set_if_unset("XDG_RUNTIME_DIR", &format!("/run/user/{uid}"));
+    process_env.set_if_unset("XDG_SESSION_ID", "1");
+}
+
+const DEFAULT_PATH: &str = "/usr/local/sbin:/usr/local/bin:/usr/bin";
+const LOGIN_DEFS_PATH: &str = "/etc/login.defs";
+const ETC_ENVIRONMENT_PATH: &str = "/etc/environment";
+
+/// Get the value of a `key` within the contents of a `login.defs` file
+fn login_defs_value<'a>(content: &'a str, key: &str) -> Option<&'a str> {
+    content.lines().find_map(|line| {
+        let line = line.trim();
+        if line.starts_with('#') {
+            return None;
+        }
+
+        let (line_key, value) = line.split_once(char::is_whitespace)?;
+        (line_key == key).then(|| value.trim())
+    })
+}
+
+/// Parse the contents of a `/etc/environment` file into its variables
+fn parse_environment_file(content: &str) -> Vec<(String, String)> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                return None;
+            }
+
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (key, value) = line.split_once('=')?;
+
+            let value = value.trim();
+            let value = ['"', '\'']
+                .iter()
+                .find_map(|quote| {
+                    value
+                        .strip_prefix(*quote)
+                        .and_then(|value| value.strip_suffix(*quote))
+                })
+                .unwrap_or(value);
+
+            Some((key.trim().to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Replace all `$VAR` and `${VAR}` references in `value` with their value in the session
+/// environment
+///
+/// Unset variables are replaced by an empty string. A `$` that does not start a variable name is
+/// kept as is.
+fn expand_variables(value: &str, process_env: &EnvironmentContainer) -> String {
+    let mut expanded = String::with_capacity(value.len());
+    let mut last_end = 0;
+
+    for var in VariableIterator::new(value) {
+        // A lone `$` or a positional parameter is not a variable
+        if var.ident().is_empty() || var.ident().starts_with(|c: char| c.is_ascii_digit()) {
+            continue;
+        }
+
+        let span = var.span();
+        expanded.push_str(&value[last_end..span.start]);
+        expanded.push_str(process_env.get(var.ident()).unwrap_or_default());
+        last_end = span.end;
+    }
+
+    expanded.push_str(&value[last_end..]);
+    expanded
 }
 
 /// Set all the environment variables
@@ -67,6 +140,8 @@ pub fn set_basic_variables(
     username: &str,
     homedir: &str,
     shell: &str,
+    uid: u32,
+    config: &SessionEnvConfig,
 ) {
     info!("Setting Basic Environment Variables");
 
@@ -77,19 +152,180 @@ pub fn set_basic_variables(
     process_env.set("SHELL", shell);
     process_env.set("USER", username);
     process_env.set("LOGNAME", username);
-    process_env.set("PATH", "/usr/local/sbin:/usr/local/bin:/usr/bin");
 
-    // process_env.set("MAIL", "..."); TODO: Add
+    let login_defs = if config.read_login_defs {
+        fs::read_to_string(LOGIN_DEFS_PATH).unwrap_or_default()
+    } else {
+        String::new()
+    };
+
+    // A `PATH` from `/etc/environment` or PAM takes precedence
+    let path_key = if uid == 0 { "ENV_SUPATH" } else { "ENV_PATH" };
+    let path = login_defs_value(&login_defs, path_key)
+        .map(|path| path.strip_prefix("PATH=").unwrap_or(path))
+        .unwrap_or(DEFAULT_PATH);
+    process_env.set_if_unset("PATH", path);
+
+    if let Some(mail_dir) = login_defs_value(&login_defs, "MAIL_DIR") {
+        process_env.set_if_unset("MAIL", format!("{mail_dir}/{username}"));
+    }
+}
+
+fn apply_session_env_rules(
+    process_env: &mut EnvironmentContainer,
+    set: &SessionEnvList,
+    unset: &SessionEnvList,
+    append_path: &SessionEnvList,
+) {
+    for key in &unset.0 {
+        process_env.remove_var(key);
+    }
+
+    for variable in &set.0 {
+        match variable.split_once('=') {
+            Some((key, value)) => {
+                let value = expand_variables(value, process_env);
+                process_env.set(key.trim(), value);
+            }
+            None => warn!(
+                "Ignoring session environment variable '{variable}', because it is not of the form 'KEY=VALUE'"
+            ),
+        }
+    }
+
+    for directory in &append_path.0 {
+        let directory = expand_variables(directory, process_env);
+        let path = process_env.get("PATH").unwrap_or_default();
+
+        if path.split(':').any(|entry| entry == directory) {
+            continue;
+        }
+
+        let path = if path.is_empty() {
+            directory
+        } else {
+            format!("{path}:{directory}")
+        };
+        process_env.set("PATH", path);
+    }
+}
+
+/// Set the variables from `/etc/environment`
+///
+/// This happens before the PAM session is opened, so the variables that PAM and Lemurs set for
+/// the session take precedence over the file.
+pub fn set_etc_environment(process_env: &mut EnvironmentContainer, config: &SessionEnvConfig) {
+    if !config.read_etc_environment {
+        return;
+    }
+
+    info!("Setting Variables from `{ETC_ENVIRONMENT_PATH}`");
+
+    match fs::read_to_string(ETC_ENVIRONMENT_PATH) {
+        Ok(content) => {
+            for (key, value) in parse_environment_file(&content) {
+                process_env.set(key, value);
+            }
+        }
+        Err(err) => info!("Skipping `{ETC_ENVIRONMENT_PATH}`. Reason: {err}"),
+    }
+}
+
+/// Apply the configured session environment for the environment with `environment_name`
+pub fn set_session_env(
+    process_env: &mut EnvironmentContainer,
+    config: &SessionEnvConfig,
+    environment_name: &str,
+) {
+    info!("Setting Configured Session Environment Variables");
+
+    apply_session_env_rules(process_env, &config.set, &config.unset, &config.append_path);
+
+    for env_override in config
+        .overrides
+        .0
+        .iter()
+        .filter(|env_override| env_override.environment == environment_name)
+    {
+        info!("Applying session environment override for '{environment_name}'");
+
+        apply_session_env_rules(
+            process_env,
+            &env_override.set,
+            &env_override.unset,
+            &env_override.append_path,
+        );
+    }
+}
+
+/// Set the locale selected in the locale switcher as the language of the session
+///
+/// `LC_ALL` and `LANGUAGE` would take precedence over `LANG`, so they are removed. Other `LC_*`
+/// variables are kept, since they were set for a specific category.
+pub fn set_locale(process_env: &mut EnvironmentContainer, locale: &str) {
+    info!("Setting session locale to '{locale}'");
+
+    process_env.remove_var("LC_ALL");
+    process_env.remove_var("LANGUAGE");
+    process_env.set("LANG", locale);
 }
 
 pub fn set_xdg_common_paths(process_env: &mut EnvironmentContainer, homedir: &str) {
     info!("Setting XDG Common Paths");
 
     // This is according to https://wiki.archlinux.org/title/XDG_Base_Directory
-    process_env.set_or_own("XDG_CONFIG_HOME", &format!("{homedir}/.config"));
-    process_env.set_or_own("XDG_CACHE_HOME", &format!("{homedir}/.cache"));
-    process_env.set_or_own("XDG_DATA_HOME", &format!("{homedir}/.local/share"));
-    process_env.set_or_own("XDG_STATE_HOME", &format!("{homedir}/.local/state"));
-    process_env.set_or_own("XDG_DATA_DIRS", "/usr/local/share:/usr/share");
-    process_env.set_or_own("XDG_CONFIG_DIRS", "/etc/xdg");
+    process_env.set_if_unset("XDG_CONFIG_HOME", &format!("{homedir}/.config"));
+    process_env.set_if_unset("XDG_CACHE_HOME", &format!("{homedir}/.cache"));
+    process_env.set_if_unset("XDG_DATA_HOME", &format!("{homedir}/.local/share"));
+    process_env.set_if_unset("XDG_STATE_HOME", &format!("{homedir}/.local/state"));
+    process_env.set_if_unset("XDG_DATA_DIRS", "/usr/local/share:/usr/share");
+    process_env.set_if_unset("XDG_CONFIG_DIRS", "/etc/xdg");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{expand_variables, login_defs_value, parse_environment_file};
+    use crate::env_container::EnvironmentContainer;
+
+    #[test]
+    fn variable_expansion() {
+        let mut process_env = EnvironmentContainer::default();
+        process_env.set("HOME", "/home/lemur");
+
+        assert_eq!(
+            expand_variables("$HOME/.local/bin", &process_env),
+            "/home/lemur/.local/bin"
+        );
+        assert_eq!(expand_variables("$UNSET:a", &process_env), ":a");
+        assert_eq!(expand_variables("cost: 5$", &process_env), "cost: 5$");
+        assert_eq!(
+            expand_variables("${HOME}bin:$$:${}:${HOME", &process_env),
+            "/home/lemurbin:$$:${}:${HOME"
+        );
+        assert_eq!(
+            expand_variables("\"$HOME\"", &process_env),
+            "\"/home/lemur\""
+        );
+    }
+
+    #[test]
+    fn system_files() {
+        let login_defs =
+            "# ENV_PATH commented\nENV_SUPATH\tPATH=/sbin:/bin\nENV_PATH   PATH=/bin\n";
+        assert_eq!(login_defs_value(login_defs, "ENV_PATH"), Some("PATH=/bin"));
+        assert_eq!(
+            login_defs_value(login_defs, "ENV_SUPATH"),
+            Some("PATH=/sbin:/bin")
+        );
+        assert_eq!(login_defs_value(login_defs, "MAIL_DIR"), None);
+
+        let environment = "# comment\n\nLANG=en_US.UTF-8\nexport EDITOR=\"vim\"\nINVALID\n";
+        assert_eq!(
+            parse_environment_file(environment),
+            [
+                ("LANG".to_string(), "en_US.UTF-8".to_string()),
+                ("EDITOR".to_string(), "vim".to_string())
+            ]
+        );
+    }
 }
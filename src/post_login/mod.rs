@@ -2,30 +2,56 @@ use log::{error, info, warn};
 use std::error::Error;
 use std::fmt::Display;
 use std::fs;
-use std::path::Path;
+use std::io::Read;
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::path::{Component, Path, PathBuf};
 
 use std::os::unix::process::CommandExt;
 use std::process::{Child, Command, Stdio};
 
 use crate::auth::AuthUserInfo;
-use crate::config::{Config, ShellLoginFlag};
+use crate::config::{
+    Config, CustomEnvironment, SessionShutdownConfig, SessionType, ShellLoginFlag, WaylandReadiness,
+};
 use crate::env_container::EnvironmentContainer;
-use crate::post_login::x::setup_x;
+use crate::post_login::x::{
+    remove_server_auth_file, setup_x, setup_xwayland_auth, wait_for_x_socket, XServer,
+};
 
 use nix::unistd::{Gid, Uid};
+use uzers::os::unix::UserExt;
 
+use self::shutdown::{clean_up_orphans, kill_and_reap, terminate_child, terminate_process_group};
 use self::wait_with_log::LemursChild;
+use self::wayland::WaylandSocketWatcher;
 use self::x::XSetupError;
 
 pub(crate) mod env_variables;
+pub(crate) mod shutdown;
 mod wait_with_log;
-mod x;
+mod wayland;
+pub(crate) mod x;
+mod x_ready;
+mod xauth;
 
 #[derive(Debug, Clone)]
 pub enum PostLoginEnvironment {
     X { xinitrc_path: String },
     Wayland { script_path: String },
     Shell,
+    Custom(CustomEnvironment),
+    XwaylandKiosk { compositor: String, client: String },
+}
+
+impl SessionType {
+    pub fn to_xdg_type(&self) -> &'static str {
+        match self {
+            Self::Tty => "tty",
+            Self::X11 => "x11",
+            Self::Wayland => "wayland",
+            Self::Mir => "mir",
+        }
+    }
 }
 
 impl PostLoginEnvironment {
@@ -34,6 +60,26 @@ impl PostLoginEnvironment {
             Self::Shell => "tty",
             Self::X { .. } => "x11",
             Self::Wayland { .. } => "wayland",
+            Self::Custom(custom) => custom.session_type.to_xdg_type(),
+            Self::XwaylandKiosk { .. } => "wayland",
+        }
+    }
+
+    /// Whether the environment needs an X server to be started
+    pub fn uses_x_server(&self) -> bool {
+        match self {
+            Self::X { .. } | Self::XwaylandKiosk { .. } => true,
+            Self::Custom(custom) => matches!(custom.session_type, SessionType::X11),
+            Self::Wayland { .. } | Self::Shell => false,
+        }
+    }
+
+    /// Whether the environment runs directly on the console of the TTY
+    pub fn uses_console(&self) -> bool {
+        match self {
+            Self::Shell => true,
+            Self::Custom(custom) => matches!(custom.session_type, SessionType::Tty),
+            Self::X { .. } | Self::Wayland { .. } | Self::XwaylandKiosk { .. } => false,
         }
     }
 
@@ -46,18 +92,28 @@ impl PostLoginEnvironment {
 #[derive(Debug, Clone)]
 pub enum EnvironmentStartError {
     WaylandStart,
+    WaylandTimeout,
+    WaylandPrematureExit,
     XSetup(XSetupError),
     XStartEnv,
     TTYStart,
+    CustomStart,
+    ConsoleRestore,
 }
 
 impl Display for EnvironmentStartError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::WaylandStart => f.write_str("Failed to start Wayland compositor"),
+            Self::WaylandTimeout => {
+                f.write_str("Timeout while waiting for Wayland compositor to start")
+            }
+            Self::WaylandPrematureExit => f.write_str("Wayland compositor exited during startup"),
             Self::XSetup(err) => write!(f, "Failed to setup X11 server. Reason: '{err}'"),
             Self::XStartEnv => f.write_str("Failed to start X11 client"),
             Self::TTYStart => f.write_str("Failed to start TTY"),
+            Self::CustomStart => f.write_str("Failed to start custom environment"),
+            Self::ConsoleRestore => f.write_str("Failed to restore the console font"),
         }
     }
 }
@@ -69,9 +125,9 @@ impl From<XSetupError> for EnvironmentStartError {
     }
 }
 
-fn lower_command_permissions_to_user(
+pub(crate) fn lower_command_permissions_to_user(
     mut command: Command,
-    user_info: &AuthUserInfo<'_>,
+    user_info: &AuthUserInfo,
 ) -> Command {
     let uid = user_info.uid;
     let gid = user_info.primary_gid;
@@ -96,66 +152,119 @@ fn lower_command_permissions_to_user(
     command
 }
 
+/// Spawn a Wayland compositor and, if configured, wait until it accepts clients
+fn spawn_wayland_compositor(
+    command: Command,
+    process_env: &EnvironmentContainer,
+    log_path: Option<&Path>,
+    config: &Config,
+) -> Result<LemursChild, EnvironmentStartError> {
+    let watcher = match config.wayland.readiness {
+        WaylandReadiness::None => None,
+        WaylandReadiness::Socket => match process_env.get("XDG_RUNTIME_DIR") {
+            Some(runtime_dir) => match WaylandSocketWatcher::new(Path::new(runtime_dir)) {
+                Ok(watcher) => Some(watcher),
+                Err(err) => {
+                    warn!("Failed to watch `{runtime_dir}` for the Wayland socket. Skipping readiness detection. Reason: {err}");
+                    None
+                }
+            },
+            None => {
+                warn!("`XDG_RUNTIME_DIR` is not set. Skipping readiness detection");
+                None
+            }
+        },
+    };
+
+    let mut child = match LemursChild::spawn(command, log_path) {
+        Ok(child) => child,
+        Err(err) => {
+            error!("Failed to start Wayland Compositor. Reason '{err}'");
+            return Err(EnvironmentStartError::WaylandStart);
+        }
+    };
+
+    if let Some(watcher) = watcher {
+        watcher.wait(&mut child, config.wayland.startup_timeout_secs)?;
+    }
+
+    Ok(child)
+}
+
 pub enum SpawnedEnvironment {
     X11 {
-        server: LemursChild,
+        server: XServer,
         client: LemursChild,
     },
     Wayland(LemursChild),
+    XwaylandKiosk {
+        compositor: LemursChild,
+        client: LemursChild,
+        server_auth_path: PathBuf,
+    },
     Tty(Child),
 }
 
 impl SpawnedEnvironment {
     pub fn pid(&self) -> u32 {
         match self {
-            Self::X11 { client, .. } | Self::Wayland(client) => client.id(),
+            Self::X11 { client, .. }
+            | Self::Wayland(client)
+            | Self::XwaylandKiosk { client, .. } => client.id(),
             Self::Tty(client) => client.id(),
         }
     }
 
-    pub fn wait(self) {
+    pub fn wait(self, config: &SessionShutdownConfig) {
         info!("Waiting for client to exit");
 
+        let wait_for_client = |client: &mut LemursChild| {
+            match client.wait() {
+                Ok(exit_code) => info!("Client exited with exit code `{exit_code}`"),
+                Err(err) => error!("Failed to wait for client. Reason: {err}"),
+            };
+
+            // The client was started in its own process group
+            if config.kill_remaining_processes {
+                terminate_process_group(client.id(), config);
+            }
+        };
+
         match self {
             Self::X11 {
                 mut client,
                 mut server,
             } => {
-                match client.wait() {
-                    Ok(exit_code) => info!("Client exited with exit code `{exit_code}`"),
-                    Err(err) => {
-                        error!("Failed to wait for client. Reason: {err}");
-                    }
-                };
-
-                info!("Telling X server to shut down");
-                match server.send_sigterm() {
-                    Ok(_) => {}
-                    Err(err) => error!("Failed to terminate X11. Reason: {err}"),
-                }
-
-                info!("Waiting for X server");
-                match server.wait() {
-                    Ok(_) => {}
-                    Err(err) => error!("Failed to wait for X11. Reason: {err}"),
-                }
+                wait_for_client(&mut client);
+                terminate_child(&mut server.process, "X server", config);
+                server.remove_auth_file();
             }
-            Self::Wayland(mut client) => match client.wait() {
-                Ok(exit_code) => info!("Client exited with exit code `{exit_code}`"),
-                Err(err) => error!("Failed to wait for client. Reason: {err}"),
-            },
+            Self::XwaylandKiosk {
+                mut client,
+                mut compositor,
+                server_auth_path,
+            } => {
+                wait_for_client(&mut client);
+                terminate_child(&mut compositor, "Wayland compositor", config);
+                remove_server_auth_file(&server_auth_path);
+            }
+            Self::Wayland(mut client) => wait_for_client(&mut client),
+            // A TTY session stays within the foreground process group of the terminal, which is
+            // the process group of Lemurs itself. Only its orphans are terminated below.
             Self::Tty(mut client) => match client.wait() {
                 Ok(exit_code) => info!("Client exited with exit code `{exit_code}`"),
                 Err(err) => error!("Failed to wait for client. Reason: {err}"),
             },
         }
+
+        clean_up_orphans(config);
     }
 }
 
 impl PostLoginEnvironment {
     pub fn spawn(
         &self,
-        user_info: &AuthUserInfo<'_>,
+        user_info: &AuthUserInfo,
         process_env: &mut EnvironmentContainer,
         config: &Config,
     ) -> Result<SpawnedEnvironment, EnvironmentStartError> {
@@ -165,30 +274,40 @@ impl PostLoginEnvironment {
             ShellLoginFlag::Long => Some("--login"),
         };
 
-        let mut client =
-            lower_command_permissions_to_user(Command::new(&config.system_shell), user_info);
+        // The environment is only applied when the command is created. So this should be called
+        // once `process_env` is complete.
+        let shell_command = |process_env: &EnvironmentContainer| {
+            let mut command =
+                lower_command_permissions_to_user(Command::new(&config.system_shell), user_info);
+            process_env.apply(&mut command);
 
-        let log_path = config.do_log.then_some(Path::new(&config.client_log_path));
+            if let Some(shell_login_flag) = shell_login_flag {
+                command.arg(shell_login_flag);
+            }
 
-        if let Some(shell_login_flag) = shell_login_flag {
-            client.arg(shell_login_flag);
-        }
+            command.arg("-c");
+            command
+        };
 
-        client.arg("-c");
+        let log_path = config.do_log.then_some(Path::new(&config.client_log_path));
 
         match self {
             PostLoginEnvironment::X { xinitrc_path } => {
                 info!("Starting X11 session");
 
-                let server = setup_x(process_env, user_info, config)
+                let mut server = setup_x(process_env, user_info, config)
                     .map_err(EnvironmentStartError::XSetup)?;
 
-                client.arg(format!("{} {}", &config.x11.xsetup_path, xinitrc_path));
+                let mut client = shell_command(process_env);
+                client
+                    .arg(format!("{} {}", &config.x11.xsetup_path, xinitrc_path))
+                    .process_group(0);
 
                 let client = match LemursChild::spawn(client, log_path) {
                     Ok(child) => child,
                     Err(err) => {
                         error!("Failed to start X11 environment. Reason '{}'", err);
+                        server.kill();
                         return Err(EnvironmentStartError::XStartEnv);
                     }
                 };
@@ -198,15 +317,10 @@ impl PostLoginEnvironment {
             PostLoginEnvironment::Wayland { script_path } => {
                 info!("Starting Wayland session");
 
-                client.arg(script_path);
+                let mut client = shell_command(process_env);
+                client.arg(script_path).process_group(0);
 
-                let child = match LemursChild::spawn(client, log_path) {
-                    Ok(child) => child,
-                    Err(err) => {
-                        error!("Failed to start Wayland Compositor. Reason '{err}'");
-                        return Err(EnvironmentStartError::WaylandStart);
-                    }
-                };
+                let child = spawn_wayland_compositor(client, process_env, log_path, config)?;
 
                 Ok(SpawnedEnvironment::Wayland(child))
             }
@@ -214,7 +328,7 @@ impl PostLoginEnvironment {
                 info!("Starting TTY shell");
 
                 let shell = &user_info.shell;
-                let child = match client
+                let child = match shell_command(process_env)
                     .arg(shell)
                     .stdout(Stdio::inherit())
                     .stderr(Stdio::inherit())
@@ -230,6 +344,180 @@ impl PostLoginEnvironment {
 
                 Ok(SpawnedEnvironment::Tty(child))
             }
+            PostLoginEnvironment::XwaylandKiosk {
+                compositor,
+                client: client_cmd,
+            } => {
+                info!("Starting Xwayland kiosk session");
+
+                // Xwayland runs as the user, so it only accepts clients with the cookie
+                let server_auth_path = setup_xwayland_auth(process_env, user_info, config)?;
+
+                let display = process_env
+                    .get("DISPLAY")
+                    .ok_or(EnvironmentStartError::XSetup(XSetupError::DisplayEnvVar))?;
+
+                // The compositor should not try to connect to the X display it is going to host.
+                // It gets its own process group, so that Xwayland is killed together with it. The
+                // Xwayland command is passed as positional arguments, so it needs no quoting.
+                let mut compositor_command = shell_command(process_env);
+                compositor_command
+                    .arg(format!("{compositor} \"$@\""))
+                    .arg("sh")
+                    .arg(&config.xwayland_kiosk.xwayland_path)
+                    .arg(display)
+                    .arg("-auth")
+                    .arg(&server_auth_path)
+                    .env_remove("DISPLAY")
+                    .process_group(0);
+
+                let xserver_log_path = config
+                    .do_log
+                    .then_some(Path::new(&config.x11.xserver_log_path));
+
+                let mut compositor = match LemursChild::spawn(compositor_command, xserver_log_path)
+                {
+                    Ok(child) => child,
+                    Err(err) => {
+                        error!("Failed to start Wayland Compositor. Reason '{err}'");
+                        remove_server_auth_file(&server_auth_path);
+                        return Err(EnvironmentStartError::WaylandStart);
+                    }
+                };
+
+                if let Err(err) =
+                    wait_for_x_socket(display, &mut compositor, config.x11.xserver_timeout_secs)
+                {
+                    kill_and_reap(&mut compositor, "Wayland compositor");
+                    // Xwayland might have left the process group of the compositor
+                    clean_up_orphans(&config.session_shutdown);
+                    remove_server_auth_file(&server_auth_path);
+                    return Err(EnvironmentStartError::XSetup(err));
+                }
+
+                // The client should use the Xwayland server and not the compositor directly
+                let mut client = shell_command(process_env);
+                client
+                    .arg(client_cmd)
+                    .env("DISPLAY", display)
+                    .env_remove("WAYLAND_DISPLAY")
+                    .process_group(0);
+
+                let client = match LemursChild::spawn(client, log_path) {
+                    Ok(child) => child,
+                    Err(err) => {
+                        error!("Failed to start Xwayland client. Reason '{err}'");
+
+                        kill_and_reap(&mut compositor, "Wayland compositor");
+                        clean_up_orphans(&config.session_shutdown);
+                        remove_server_auth_file(&server_auth_path);
+                        return Err(EnvironmentStartError::XStartEnv);
+                    }
+                };
+
+                Ok(SpawnedEnvironment::XwaylandKiosk {
+                    compositor,
+                    client,
+                    server_auth_path,
+                })
+            }
+            PostLoginEnvironment::Custom(custom) => {
+                info!("Starting custom environment '{}'", custom.name);
+
+                // The X server needs to be set up first, as it adds to the environment
+                let server = match custom.session_type {
+                    SessionType::X11 => Some(
+                        setup_x(process_env, user_info, config)
+                            .map_err(EnvironmentStartError::XSetup)?,
+                    ),
+                    _ => None,
+                };
+
+                let mut client = shell_command(process_env);
+
+                // Pass the command and arguments as positional parameters so they are not
+                // interpreted by the shell.
+                client.arg("exec \"$0\" \"$@\"");
+
+                if matches!(custom.session_type, SessionType::X11) {
+                    client.arg(&config.x11.xsetup_path);
+                }
+
+                client.arg(&custom.cmd).args(&custom.args);
+
+                if !custom.working_dir.is_empty() {
+                    client.current_dir(&custom.working_dir);
+                }
+
+                for variable in &custom.env {
+                    match variable.split_once('=') {
+                        Some((key, value)) => {
+                            client.env(key, value);
+                        }
+                        None => warn!(
+                            "Ignoring environment variable '{variable}' of '{}', because it is not of the form 'KEY=VALUE'",
+                            custom.name
+                        ),
+                    }
+                }
+
+                // A TTY session needs to stay in the foreground process group of the terminal
+                if !matches!(custom.session_type, SessionType::Tty) {
+                    client.process_group(0);
+                }
+
+                match custom.session_type {
+                    SessionType::Tty => {
+                        let child = match client
+                            .stdout(Stdio::inherit())
+                            .stderr(Stdio::inherit())
+                            .stdin(Stdio::inherit())
+                            .spawn()
+                        {
+                            Ok(child) => child,
+                            Err(err) => {
+                                error!("Failed to start custom TTY environment. Reason '{err}'");
+                                return Err(EnvironmentStartError::CustomStart);
+                            }
+                        };
+
+                        Ok(SpawnedEnvironment::Tty(child))
+                    }
+                    SessionType::X11 => {
+                        let Some(mut server) = server else {
+                            return Err(EnvironmentStartError::CustomStart);
+                        };
+
+                        let client = match LemursChild::spawn(client, log_path) {
+                            Ok(child) => child,
+                            Err(err) => {
+                                error!("Failed to start custom X11 environment. Reason '{err}'");
+                                server.kill();
+                                return Err(EnvironmentStartError::CustomStart);
+                            }
+                        };
+
+                        Ok(SpawnedEnvironment::X11 { server, client })
+                    }
+                    SessionType::Wayland => {
+                        let child =
+                            spawn_wayland_compositor(client, process_env, log_path, config)?;
+
+                        Ok(SpawnedEnvironment::Wayland(child))
+                    }
+                    SessionType::Mir => {
+                        let child = match LemursChild::spawn(client, log_path) {
+                            Ok(child) => child,
+                            Err(err) => {
+                                error!("Failed to start custom environment. Reason '{err}'");
+                                return Err(EnvironmentStartError::CustomStart);
+                            }
+                        };
+
+                        Ok(SpawnedEnvironment::Wayland(child))
+                    }
+                }
+            }
         }
     }
 }
@@ -242,7 +530,11 @@ fn parse_desktop_entry(path: &Path, _: &Config) -> Result<(String, String), Stri
         }
     };
 
-    let desktop_entry = match deentry::DesktopEntry::try_from(&content[..]) {
+    parse_desktop_entry_content(path, &content)
+}
+
+fn parse_desktop_entry_content(path: &Path, content: &str) -> Result<(String, String), String> {
+    let desktop_entry = match deentry::DesktopEntry::try_from(content) {
         Ok(v) => v,
         Err(err) => {
             return Err(format!("file cannot be parsed. Reason: {err}"));
@@ -435,6 +727,38 @@ pub fn get_envs(config: &Config) -> Vec<(String, PostLoginEnvironment)> {
         }
     }
 
+    for custom in &config.custom_environments.entries.0 {
+        if custom.name.is_empty() || custom.cmd.is_empty() {
+            warn!("Skipping custom environment without a 'name' or 'cmd'");
+            continue;
+        }
+
+        info!(
+            "Added environment '{}' from custom environments",
+            custom.name
+        );
+        envs.push((
+            custom.name.clone(),
+            PostLoginEnvironment::Custom(custom.clone()),
+        ));
+    }
+
+    for kiosk in &config.xwayland_kiosk.entries.0 {
+        if kiosk.name.is_empty() || kiosk.compositor.is_empty() || kiosk.client.is_empty() {
+            warn!("Skipping Xwayland kiosk without a 'name', 'compositor' or 'client'");
+            continue;
+        }
+
+        info!("Added environment '{}' from Xwayland kiosks", kiosk.name);
+        envs.push((
+            kiosk.name.clone(),
+            PostLoginEnvironment::XwaylandKiosk {
+                compositor: kiosk.compositor.clone(),
+                client: kiosk.client.clone(),
+            },
+        ));
+    }
+
     if envs.is_empty() || config.environment_switcher.include_tty_shell {
         if envs.is_empty() {
             info!("Added TTY SHELL because no other environments were found");
@@ -445,3 +769,231 @@ pub fn get_envs(config: &Config) -> Vec<(String, PostLoginEnvironment)> {
 
     envs
 }
+
+/// Verify that a path within the user's home directory can be trusted
+///
+/// The path and all its parents up to and including the home directory need to be owned by the
+/// user or root and may not be writable by the group or others. Symbolic links are not followed.
+fn verify_user_owned_path(path: &Path, home_dir: &Path, uid: u32) -> Result<(), String> {
+    // A `..` leaves the home directory while the path still starts with it
+    if !path.starts_with(home_dir)
+        || path
+            .components()
+            .any(|component| component == Component::ParentDir)
+    {
+        return Err(format!(
+            "'{}' is not within the home directory",
+            path.display()
+        ));
+    }
+
+    for ancestor in path.ancestors() {
+        if !ancestor.starts_with(home_dir) {
+            break;
+        }
+
+        let metadata = fs::symlink_metadata(ancestor)
+            .map_err(|err| format!("'{}' cannot be read. Reason: {err}", ancestor.display()))?;
+
+        if metadata.file_type().is_symlink() {
+            return Err(format!("'{}' is a symbolic link", ancestor.display()));
+        }
+
+        if metadata.uid() != uid && metadata.uid() != 0 {
+            return Err(format!(
+                "'{}' is not owned by the user or root",
+                ancestor.display()
+            ));
+        }
+
+        if metadata.mode() & 0o022 != 0 {
+            return Err(format!(
+                "'{}' is writable by the group or others",
+                ancestor.display()
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// The largest desktop entry of a user that is read
+const MAX_USER_DESKTOP_ENTRY_SIZE: u64 = 64 * 1024;
+
+/// Read a desktop entry from the user's home directory
+///
+/// The file is checked again after it is opened, because it can be replaced after
+/// `verify_user_owned_path` has looked at it. Opening does not block on FIFOs and does not follow
+/// symbolic links. Only regular files up to `MAX_USER_DESKTOP_ENTRY_SIZE` bytes are read.
+fn read_user_desktop_entry(path: &Path, uid: u32) -> Result<String, String> {
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOFOLLOW | libc::O_NONBLOCK)
+        .open(path)
+        .map_err(|err| format!("file cannot be opened. Reason: {err}"))?;
+
+    let metadata = file
+        .metadata()
+        .map_err(|err| format!("file cannot be read. Reason: {err}"))?;
+
+    if !metadata.is_file() {
+        return Err("file is not a regular file".to_string());
+    }
+
+    if metadata.uid() != uid && metadata.uid() != 0 {
+        return Err("file is not owned by the user or root".to_string());
+    }
+
+    if metadata.mode() & 0o022 != 0 {
+        return Err("file is writable by the group or others".to_string());
+    }
+
+    if metadata.len() > MAX_USER_DESKTOP_ENTRY_SIZE {
+        return Err(format!(
+            "file is larger than {MAX_USER_DESKTOP_ENTRY_SIZE} bytes"
+        ));
+    }
+
+    // The file can still grow after it was checked
+    let mut content = String::new();
+    file.take(MAX_USER_DESKTOP_ENTRY_SIZE + 1)
+        .read_to_string(&mut content)
+        .map_err(|err| format!("file cannot be read. Reason: {err}"))?;
+
+    if content.len() as u64 > MAX_USER_DESKTOP_ENTRY_SIZE {
+        return Err(format!(
+            "file is larger than {MAX_USER_DESKTOP_ENTRY_SIZE} bytes"
+        ));
+    }
+
+    Ok(content)
+}
+
+/// The suffix of a user environment that has the same name as another environment
+const USER_ENV_SUFFIX: &str = " (user)";
+
+/// Fetch the environments that a user has defined within their home directory
+///
+/// This only returns environments when `user_sessions.enabled` is set in the configuration. A user
+/// environment with the same name as one of the `system_envs` or an earlier user environment gets
+/// the `USER_ENV_SUFFIX`, so it cannot be mistaken for it. It is skipped if that name is taken as
+/// well.
+pub fn get_user_envs(
+    config: &Config,
+    username: &str,
+    system_envs: &[(String, PostLoginEnvironment)],
+) -> Vec<(String, PostLoginEnvironment)> {
+    let mut envs: Vec<(String, PostLoginEnvironment)> = Vec::new();
+
+    if !config.user_sessions.enabled {
+        return envs;
+    }
+
+    let Some(user) = uzers::get_user_by_name(username) else {
+        info!("No user sessions loaded, because user '{username}' does not exist");
+        return envs;
+    };
+
+    let uid = user.uid();
+    let home_dir = user.home_dir();
+    let sessions_path = home_dir.join(&config.user_sessions.path);
+
+    for (kind, is_wayland) in [("x11", false), ("wayland", true)] {
+        let dir_path = sessions_path.join(kind);
+
+        if !dir_path.exists() {
+            continue;
+        }
+
+        if let Err(err) = verify_user_owned_path(&dir_path, home_dir, uid) {
+            warn!("Skipping user {kind} sessions, because {err}");
+            continue;
+        }
+
+        let paths = match fs::read_dir(&dir_path) {
+            Ok(paths) => paths,
+            Err(err) => {
+                warn!("Failed to read from the user {kind} sessions folder '{err}'");
+                continue;
+            }
+        };
+
+        for path in paths {
+            let Ok(path) = path else {
+                continue;
+            };
+
+            let path = path.path();
+
+            if let Err(err) = verify_user_owned_path(&path, home_dir, uid) {
+                warn!("Skipping '{}', because {err}", path.display());
+                continue;
+            }
+
+            let (name, exec) = if path.extension().is_some_and(|ext| ext == "desktop") {
+                match read_user_desktop_entry(&path, uid)
+                    .and_then(|content| parse_desktop_entry_content(&path, &content))
+                {
+                    Ok(entry) => entry,
+                    Err(err) => {
+                        warn!("Skipping '{}', because {err}", path.display());
+                        continue;
+                    }
+                }
+            } else {
+                let Ok(metadata) = path.metadata() else {
+                    continue;
+                };
+
+                if !metadata.is_file() || metadata.mode() & 0o111 == 0 {
+                    warn!(
+                        "'{}' is not an executable file and therefore not added as an environment",
+                        path.display()
+                    );
+                    continue;
+                }
+
+                let (Some(file_name), Some(script_path)) = (
+                    path.file_name().and_then(|name| name.to_str()),
+                    path.to_str(),
+                ) else {
+                    warn!("Skipped item because it was impossible to convert to string");
+                    continue;
+                };
+
+                (file_name.to_string(), script_path.to_string())
+            };
+
+            let is_taken = |name: &str| {
+                system_envs
+                    .iter()
+                    .chain(envs.iter())
+                    .any(|(taken, _)| taken == name)
+            };
+
+            let name = if !is_taken(&name) {
+                name
+            } else if !is_taken(&format!("{name}{USER_ENV_SUFFIX}")) {
+                format!("{name}{USER_ENV_SUFFIX}")
+            } else {
+                warn!(
+                    "Skipping '{}', because the environment '{name}' already exists",
+                    path.display()
+                );
+                continue;
+            };
+
+            info!("Added environment '{name}' from user {kind} sessions");
+            envs.push((
+                name,
+                if is_wayland {
+                    PostLoginEnvironment::Wayland { script_path: exec }
+                } else {
+                    PostLoginEnvironment::X { xinitrc_path: exec }
+                },
+            ));
+        }
+    }
+
+    envs
+}
@@ -0,0 +1,283 @@
+//! This module implements the termination of the processes of a session once it ends.
+//!
+//! Processes are asked to terminate with SIGTERM and killed with SIGKILL when they do not exit
+//! within the configured timeout. Lemurs registers itself as a child subreaper, so that processes
+//! orphaned by the session are reparented to Lemurs instead of init. This allows them to be
+//! terminated and reaped once the session is over. By then, Lemurs has waited for all processes it
+//! started itself, so every child that is left was orphaned by the session. Only the orphans that
+//! are still within the session of Lemurs, to which all processes of a session belong, are
+//! terminated. Daemons that started a session of their own are left running and only reaped.
+
+use std::fs;
+use std::io;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::{error, info, warn};
+
+use crate::config::SessionShutdownConfig;
+
+use super::wait_with_log::LemursChild;
+
+/// How often to check whether processes have exited after signaling them
+const EXIT_CHECK_INTERVAL_MILLIS: u64 = 100;
+
+/// Make processes orphaned by sessions be reparented to Lemurs
+///
+/// This only needs to be done once when Lemurs starts.
+pub fn become_subreaper() {
+    let result = unsafe { libc::prctl(libc::PR_SET_CHILD_SUBREAPER, 1, 0, 0, 0) };
+
+    if result != 0 {
+        warn!(
+            "Failed to become a child subreaper. Orphaned session processes cannot be reaped. Reason: {}",
+            io::Error::last_os_error()
+        );
+    }
+}
+
+/// Call `has_exited` until it returns `true` or `timeout_secs` have passed
+///
+/// A `timeout_secs` of 0 waits indefinitely. Returns whether `has_exited` returned `true`.
+fn wait_until(timeout_secs: u16, mut has_exited: impl FnMut() -> bool) -> bool {
+    let start_time = Instant::now();
+    let timeout = Duration::from_secs(timeout_secs.into());
+
+    loop {
+        if has_exited() {
+            return true;
+        }
+
+        if timeout_secs != 0 && start_time.elapsed() > timeout {
+            return false;
+        }
+
+        thread::sleep(Duration::from_millis(EXIT_CHECK_INTERVAL_MILLIS));
+    }
+}
+
+/// Kill `child` together with its process group and reap it
+///
+/// This is used for children that failed to start, so they are not given the chance to shut down.
+pub fn kill_and_reap(child: &mut LemursChild, name: &str) {
+    if let Err(err) = child.kill() {
+        error!("Failed to kill {name}. Reason: {err}");
+    }
+
+    if let Err(err) = child.wait() {
+        error!("Failed to wait for {name}. Reason: {err}");
+    }
+}
+
+/// Ask `child` to terminate and kill it if it does not exit in time
+pub fn terminate_child(child: &mut LemursChild, name: &str, config: &SessionShutdownConfig) {
+    info!("Telling {name} to shut down");
+    if let Err(err) = child.send_sigterm() {
+        error!("Failed to terminate {name}. Reason: {err}");
+    }
+
+    let has_exited = wait_until(config.terminate_timeout_secs, || match child.try_wait() {
+        Ok(status) => status.is_some(),
+        Err(err) => {
+            error!("Failed to wait for {name}. Reason: {err}");
+            true
+        }
+    });
+
+    if has_exited {
+        info!("{name} exited after being told to shut down");
+        return;
+    }
+
+    warn!(
+        "{name} did not exit within {} seconds. Killing it",
+        config.terminate_timeout_secs
+    );
+
+    if let Err(err) = child.kill() {
+        error!("Failed to kill {name}. Reason: {err}");
+    }
+
+    match child.wait() {
+        Ok(status) => info!("{name} was killed. Status code: {status}"),
+        Err(err) => error!("Failed to wait for {name}. Reason: {err}"),
+    }
+}
+
+/// Reap all exited children that are within the process group `pgid`
+fn reap_process_group(pgid: libc::pid_t) {
+    while unsafe { libc::waitpid(-pgid, std::ptr::null_mut(), libc::WNOHANG) } > 0 {}
+}
+
+/// Whether any process is still within the process group `pgid`
+fn process_group_exists(pgid: libc::pid_t) -> bool {
+    unsafe { libc::killpg(pgid, 0) == 0 }
+}
+
+/// Terminate all processes in the process group `pgid` and kill the ones that do not exit in time
+pub fn terminate_process_group(pgid: u32, config: &SessionShutdownConfig) {
+    let pgid = pgid as libc::pid_t;
+
+    reap_process_group(pgid);
+
+    if !process_group_exists(pgid) {
+        return;
+    }
+
+    info!("Terminating the remaining processes of the session process group `{pgid}`");
+    unsafe { libc::killpg(pgid, libc::SIGTERM) };
+
+    let has_exited = wait_until(config.terminate_timeout_secs, || {
+        reap_process_group(pgid);
+        !process_group_exists(pgid)
+    });
+
+    if has_exited {
+        info!("The session process group exited after being told to shut down");
+        return;
+    }
+
+    warn!("The session process group did not exit in time. Killing it");
+    unsafe { libc::killpg(pgid, libc::SIGKILL) };
+
+    // Killed processes may still need a moment before they can be reaped
+    if !wait_until(config.terminate_timeout_secs.max(1), || {
+        reap_process_group(pgid);
+        !process_group_exists(pgid)
+    }) {
+        error!("Processes of the session process group survived being killed");
+    }
+}
+
+/// Get the PIDs of all children of Lemurs
+fn child_pids() -> io::Result<Vec<libc::pid_t>> {
+    let own_pid = std::process::id() as libc::pid_t;
+    let mut pids = Vec::new();
+
+    for entry in fs::read_dir("/proc")? {
+        let Ok(entry) = entry else {
+            continue;
+        };
+
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse::<libc::pid_t>().ok())
+        else {
+            continue;
+        };
+
+        // The process may have already exited
+        let Ok(stat) = fs::read_to_string(entry.path().join("stat")) else {
+            continue;
+        };
+
+        // The command name may contain spaces and parentheses. The parent PID is the second field
+        // after the closing parenthesis.
+        let parent_pid = stat
+            .rsplit_once(')')
+            .and_then(|(_, fields)| fields.split_whitespace().nth(1))
+            .and_then(|ppid| ppid.parse::<libc::pid_t>().ok());
+
+        if parent_pid == Some(own_pid) {
+            pids.push(pid);
+        }
+    }
+
+    Ok(pids)
+}
+
+/// Whether `pid` is within the session of Lemurs
+///
+/// All processes started for a session are, unless they started a session of their own.
+fn in_lemurs_session(pid: libc::pid_t) -> bool {
+    let sid = unsafe { libc::getsid(pid) };
+    sid != -1 && sid == unsafe { libc::getsid(0) }
+}
+
+/// Reap the children in `pids` that have exited and return the ones that are still running
+fn reap_children(pids: Vec<libc::pid_t>) -> Vec<libc::pid_t> {
+    pids.into_iter()
+        .filter(|pid| unsafe { libc::waitpid(*pid, std::ptr::null_mut(), libc::WNOHANG) } == 0)
+        .collect()
+}
+
+/// Terminate the processes orphaned by the session if configured and otherwise only reap the ones
+/// that have already exited
+///
+/// This must only be called once all processes started for the session were waited for.
+pub fn clean_up_orphans(config: &SessionShutdownConfig) {
+    if config.kill_remaining_processes {
+        terminate_orphans(config);
+    } else {
+        reap_orphans();
+    }
+}
+
+/// Reap the processes orphaned by the session that have already exited
+fn reap_orphans() {
+    match child_pids() {
+        Ok(pids) => {
+            reap_children(pids);
+        }
+        Err(err) => error!("Failed to find orphaned session processes. Reason: {err}"),
+    }
+}
+
+/// Terminate and reap the processes that were orphaned by the session
+///
+/// This must only be called once all processes started for the session were waited for, because
+/// all remaining children of Lemurs within its session are terminated.
+fn terminate_orphans(config: &SessionShutdownConfig) {
+    let (pids, detached_pids): (Vec<_>, Vec<_>) = match child_pids() {
+        Ok(pids) => reap_children(pids)
+            .into_iter()
+            .partition(|pid| in_lemurs_session(*pid)),
+        Err(err) => {
+            error!("Failed to find orphaned session processes. Reason: {err}");
+            return;
+        }
+    };
+
+    if !detached_pids.is_empty() {
+        info!("Leaving orphaned processes with a session of their own running: {detached_pids:?}");
+    }
+
+    if pids.is_empty() {
+        return;
+    }
+
+    info!(
+        "Terminating {} orphaned session process(es): {pids:?}",
+        pids.len()
+    );
+
+    for pid in &pids {
+        unsafe { libc::kill(*pid, libc::SIGTERM) };
+    }
+
+    let mut remaining = pids;
+    let has_exited = wait_until(config.terminate_timeout_secs, || {
+        remaining = reap_children(std::mem::take(&mut remaining));
+        remaining.is_empty()
+    });
+
+    if has_exited {
+        info!("All orphaned session processes exited after being told to shut down");
+        return;
+    }
+
+    warn!("Killing orphaned session processes that did not exit in time: {remaining:?}");
+
+    for pid in &remaining {
+        unsafe { libc::kill(*pid, libc::SIGKILL) };
+    }
+
+    // Killed processes may still need a moment before they can be reaped
+    if !wait_until(config.terminate_timeout_secs.max(1), || {
+        remaining = reap_children(std::mem::take(&mut remaining));
+        remaining.is_empty()
+    }) {
+        error!("Orphaned session processes survived being killed: {remaining:?}");
+    }
+}
@@ -61,7 +61,14 @@ impl<W: io::Write> LimitSizeWriter<W> {
 ///
 /// This makes handling spawning, killing and waiting a lot easier to combine with the
 /// output log files.
-pub enum LemursChild {
+pub struct LemursChild {
+    process: LemursProcess,
+    /// Whether the child leads its own process group. Signals are then sent to the whole group,
+    /// so that processes started by a wrapper (e.g. `sh -c`) are not left behind.
+    leads_process_group: bool,
+}
+
+enum LemursProcess {
     NoLog(Child),
     Log(LimitedOutputChild),
 }
@@ -72,56 +79,106 @@ pub struct LimitedOutputChild {
 }
 
 impl LemursChild {
-    pub fn spawn(mut command: Command, log_path: Option<&Path>) -> io::Result<Self> {
-        Ok(match log_path {
-            None => Self::NoLog(
+    pub fn spawn(command: Command, log_path: Option<&Path>) -> io::Result<Self> {
+        Self::spawn_with_stdin(command, Stdio::null(), log_path)
+    }
+
+    /// Spawn the command with `stdin` as its standard input instead of `/dev/null`
+    pub fn spawn_with_stdin(
+        mut command: Command,
+        stdin: Stdio,
+        log_path: Option<&Path>,
+    ) -> io::Result<Self> {
+        let process = match log_path {
+            None => LemursProcess::NoLog(
                 command
-                    .stdin(Stdio::null())
+                    .stdin(stdin)
                     .stdout(Stdio::null())
                     .stderr(Stdio::null())
                     .spawn()?,
             ),
-            Some(log_path) => Self::Log(LimitedOutputChild::spawn(command, log_path)?),
+            Some(log_path) => {
+                LemursProcess::Log(LimitedOutputChild::spawn(command, stdin, log_path)?)
+            }
+        };
+
+        // The process group is set before the command is executed, so it is known once the spawn
+        // returns. The child is not reaped yet, so its PID cannot belong to another process.
+        let pid = match &process {
+            LemursProcess::NoLog(process) => process.id(),
+            LemursProcess::Log(process) => process.id(),
+        } as libc::pid_t;
+        let leads_process_group = unsafe { libc::getpgid(pid) } == pid;
+
+        Ok(Self {
+            process,
+            leads_process_group,
         })
     }
 
     pub fn wait(&mut self) -> io::Result<ExitStatus> {
-        match self {
-            Self::NoLog(process) => process.wait(),
-            Self::Log(process) => process.wait(),
+        match &mut self.process {
+            LemursProcess::NoLog(process) => process.wait(),
+            LemursProcess::Log(process) => process.wait(),
         }
     }
 
     pub fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
-        match self {
-            Self::NoLog(process) => process.try_wait(),
-            Self::Log(process) => process.try_wait(),
+        match &mut self.process {
+            LemursProcess::NoLog(process) => process.try_wait(),
+            LemursProcess::Log(process) => process.try_wait(),
+        }
+    }
+
+    /// Send `signal` to the process group of the child if it leads one
+    fn signal_process_group(&self, signal: libc::c_int) -> io::Result<()> {
+        if !self.leads_process_group {
+            return Ok(());
+        }
+
+        if unsafe { libc::killpg(self.id() as libc::pid_t, signal) } != 0 {
+            let err = io::Error::last_os_error();
+
+            // All processes of the group have already exited
+            if err.raw_os_error() != Some(libc::ESRCH) {
+                return Err(err);
+            }
         }
+
+        Ok(())
     }
 
+    /// Kill the child together with its process group if it leads one
     pub fn kill(&mut self) -> io::Result<()> {
-        match self {
-            Self::NoLog(process) => process.kill(),
-            Self::Log(process) => process.kill(),
+        self.signal_process_group(libc::SIGKILL)?;
+
+        match &mut self.process {
+            LemursProcess::NoLog(process) => process.kill(),
+            LemursProcess::Log(process) => process.kill(),
         }
     }
 
+    /// Ask the child to terminate together with its process group if it leads one
     pub fn send_sigterm(&self) -> io::Result<()> {
+        if self.leads_process_group {
+            return self.signal_process_group(libc::SIGTERM);
+        }
+
         unsafe { libc::kill(self.id() as libc::pid_t, libc::SIGTERM) };
 
         Ok(())
     }
 
     pub fn id(&self) -> u32 {
-        match self {
-            Self::NoLog(process) => process.id(),
-            Self::Log(process) => process.id(),
+        match &self.process {
+            LemursProcess::NoLog(process) => process.id(),
+            LemursProcess::Log(process) => process.id(),
         }
     }
 }
 
 impl LimitedOutputChild {
-    pub fn spawn(mut command: Command, log_path: &Path) -> io::Result<Self> {
+    pub fn spawn(mut command: Command, stdin: Stdio, log_path: &Path) -> io::Result<Self> {
         const STDOUT_PIPE_RECV: Token = Token(0);
         const STDERR_PIPE_RECV: Token = Token(1);
         const WAKER_TOKEN: Token = Token(2);
@@ -130,7 +187,7 @@ impl LimitedOutputChild {
         let mut events = Events::with_capacity(128);
 
         command
-            .stdin(Stdio::null())
+            .stdin(stdin)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
 
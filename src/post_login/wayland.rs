@@ -0,0 +1,167 @@
+//! This module implements the detection of a Wayland compositor being ready to accept clients.
+//!
+//! Wayland compositors create a `wayland-N` socket within the `XDG_RUNTIME_DIR` once they accept
+//! connections. We watch that directory with inotify and consider the compositor started when a
+//! new socket appears.
+
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use log::{error, info, warn};
+use mio::unix::SourceFd;
+use mio::{Events, Interest, Poll, Token};
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify};
+
+use super::shutdown::kill_and_reap;
+use super::wait_with_log::LemursChild;
+use super::EnvironmentStartError;
+
+/// How often to check whether the compositor has exited while waiting for its socket
+const CHILD_CHECK_INTERVAL_MILLIS: u64 = 100;
+
+pub struct WaylandSocketWatcher {
+    inotify: Inotify,
+    runtime_dir: PathBuf,
+    existing_sockets: HashSet<OsString>,
+}
+
+fn wayland_sockets(runtime_dir: &Path) -> io::Result<HashSet<OsString>> {
+    let mut sockets = HashSet::new();
+
+    for entry in fs::read_dir(runtime_dir)? {
+        let entry = entry?;
+
+        let file_name = entry.file_name();
+        let is_wayland_socket = file_name
+            .to_str()
+            .and_then(|name| name.strip_prefix("wayland-"))
+            .is_some_and(|suffix| !suffix.ends_with(".lock"));
+
+        if is_wayland_socket && entry.file_type()?.is_socket() {
+            sockets.insert(file_name);
+        }
+    }
+
+    Ok(sockets)
+}
+
+impl WaylandSocketWatcher {
+    /// Start watching the runtime directory
+    ///
+    /// This should be called before the compositor is spawned, so that no socket creation is
+    /// missed.
+    pub fn new(runtime_dir: &Path) -> io::Result<Self> {
+        let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)?;
+
+        let mut watcher = Self {
+            inotify,
+            runtime_dir: runtime_dir.to_path_buf(),
+            existing_sockets: HashSet::new(),
+        };
+
+        watcher
+            .inotify
+            .add_watch(runtime_dir, AddWatchFlags::IN_CREATE)?;
+        watcher.existing_sockets = wayland_sockets(runtime_dir)?;
+
+        Ok(watcher)
+    }
+
+    fn find_new_socket(&self) -> Option<OsString> {
+        match wayland_sockets(&self.runtime_dir) {
+            Ok(sockets) => sockets
+                .into_iter()
+                .find(|socket| !self.existing_sockets.contains(socket)),
+            Err(err) => {
+                warn!("Failed to read the runtime directory. Reason: {err}");
+                None
+            }
+        }
+    }
+
+    /// Wait for the compositor to create its socket
+    ///
+    /// A `timeout_secs` of 0 waits indefinitely.
+    pub fn wait(
+        self,
+        child: &mut LemursChild,
+        timeout_secs: u16,
+    ) -> Result<(), EnvironmentStartError> {
+        const INOTIFY_TOKEN: Token = Token(0);
+
+        let setup_poll = || -> io::Result<Poll> {
+            let poll = Poll::new()?;
+            poll.registry().register(
+                &mut SourceFd(&self.inotify.as_raw_fd()),
+                INOTIFY_TOKEN,
+                Interest::READABLE,
+            )?;
+            Ok(poll)
+        };
+
+        let mut poll = match setup_poll() {
+            Ok(poll) => poll,
+            Err(err) => {
+                warn!("Failed to watch for the Wayland socket. Skipping readiness detection. Reason: {err}");
+                return Ok(());
+            }
+        };
+        let mut events = Events::with_capacity(16);
+
+        let start_time = Instant::now();
+        let timeout = (timeout_secs != 0).then(|| Duration::from_secs(timeout_secs.into()));
+
+        loop {
+            if let Some(socket) = self.find_new_socket() {
+                info!(
+                    "Wayland compositor created socket `{socket}` after {start_ms}ms",
+                    socket = socket.to_string_lossy(),
+                    start_ms = start_time.elapsed().as_millis()
+                );
+
+                return Ok(());
+            }
+
+            if let Some(status) = child.try_wait().unwrap_or(None) {
+                error!("Wayland compositor exited before it created its socket. Status code: {status}.");
+
+                return Err(EnvironmentStartError::WaylandPrematureExit);
+            }
+
+            if timeout.is_some_and(|timeout| start_time.elapsed() > timeout) {
+                kill_and_reap(child, "Wayland compositor");
+
+                return Err(EnvironmentStartError::WaylandTimeout);
+            }
+
+            let poll_result = poll.poll(
+                &mut events,
+                Some(Duration::from_millis(CHILD_CHECK_INTERVAL_MILLIS)),
+            );
+
+            if let Err(err) = poll_result {
+                if err.kind() != io::ErrorKind::Interrupted {
+                    warn!("Failed to wait for the Wayland socket. Skipping readiness detection. Reason: {err}");
+                    return Ok(());
+                }
+            }
+
+            // Drain the inotify events. We only use them as a wake-up.
+            if !events.is_empty() {
+                let _ = self.inotify.read_events();
+            }
+        }
+    }
+}
+
+impl Drop for WaylandSocketWatcher {
+    fn drop(&mut self) {
+        let _ = nix::unistd::close(self.inotify.as_raw_fd());
+    }
+}
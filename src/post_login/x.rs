@@ -1,207 +1,853 @@
-use libc::{signal, SIGUSR1, SIG_DFL, SIG_IGN};
-use rand::Rng;
-
-use once_cell::sync::Lazy;
-
-use std::env;
 use std::error::Error;
 use std::fmt::Display;
-use std::fs::remove_file;
-use std::process::{Command, Stdio};
-use std::sync::atomic::AtomicBool;
-use std::{thread, time};
+use std::fs::{self, remove_file};
+use std::io;
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixStream;
+use std::process::Command;
+use std::time;
 
 use std::path::{Path, PathBuf};
 
-use log::{error, info};
+use log::{error, info, warn};
+use mio::unix::SourceFd;
+use mio::{Events, Interest, Poll, Token};
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify};
 
 use crate::auth::AuthUserInfo;
 use crate::config::Config;
 use crate::env_container::EnvironmentContainer;
+use crate::post_login::lower_command_permissions_to_user;
+use crate::post_login::shutdown::kill_and_reap;
 use crate::post_login::wait_with_log::LemursChild;
+use crate::post_login::x_ready::{ignore_sigusr1_in_child, XReadyWatcher};
+use crate::post_login::xauth::{write_xauthority, Cookie};
 
+/// How often to check whether the X server accepts connections or has exited while waiting for it
 const XSTART_CHECK_INTERVAL_MILLIS: u64 = 100;
 
+/// The value of `x11_display` to automatically select a free display
+const AUTO_DISPLAY: &str = "auto";
+/// The highest display number that is considered when looking for a free display
+const MAX_DISPLAY_NUMBER: u32 = 64;
+
 #[derive(Debug, Clone)]
 pub enum XSetupError {
     DisplayEnvVar,
+    InvalidDisplay,
     HomeEnvVar,
+    RuntimeDirEnvVar,
     VTNREnvVar,
     FillingXAuth,
+    ServerAuth,
     InvalidUTF8Path,
     XServerStart,
     XServerTimeout,
     XServerPrematureExit,
+    ReadinessWait,
+    NoFreeDisplay,
 }
 
 impl Display for XSetupError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::DisplayEnvVar => f.write_str("`DISPLAY` is not set"),
+            Self::InvalidDisplay => f.write_str("`DISPLAY` is not a valid local display"),
             Self::HomeEnvVar => f.write_str("`HOME` is not set"),
+            Self::RuntimeDirEnvVar => f.write_str("`XDG_RUNTIME_DIR` is not set"),
             Self::VTNREnvVar => f.write_str("`XDG_VTNR` is not set"),
             Self::FillingXAuth => f.write_str("Failed to fill `.Xauthority` file"),
+            Self::ServerAuth => f.write_str("Failed to create the X server authorization file"),
             Self::InvalidUTF8Path => f.write_str("Path that is given is not valid UTF8"),
             Self::XServerStart => f.write_str("Failed to start X server binary"),
             Self::XServerTimeout => f.write_str("Timeout while waiting for X server to start"),
             Self::XServerPrematureExit => {
                 f.write_str("X server exited before it signaled to accept connections")
             }
+            Self::ReadinessWait => f.write_str("Failed to wait for the X server to start"),
+            Self::NoFreeDisplay => f.write_str("Failed to find a free X display"),
         }
     }
 }
 
 impl Error for XSetupError {}
 
-fn mcookie() -> String {
-    // TODO: Verify that this is actually safe. Maybe just use the mcookie binary?? Is that always
-    // available?
-    let mut rng = rand::thread_rng();
-    let cookie: u128 = rng.gen();
-    format!("{cookie:032x}")
+/// The directory in which instances of Lemurs reserve X displays
+const DISPLAY_RESERVATION_DIR: &str = "/run/lemurs/displays";
+
+/// A display that was reserved by this instance of Lemurs
+///
+/// Instances of Lemurs reserve a display by holding an exclusive `flock` on a file of their own.
+/// The X server refuses to use a display that is locked by another running process, so its lock
+/// file is left to the X server. The reservation should be held until the X server accepts
+/// connections and is released when it is dropped or Lemurs exits.
+pub struct DisplayReservation {
+    _reservation_file: fs::File,
+}
+
+/// Reserve display `number` within `reservation_dir` for this instance of Lemurs
+///
+/// Returns `None` if another instance of Lemurs holds the reservation.
+fn reserve_display(reservation_dir: &Path, number: u32) -> io::Result<Option<DisplayReservation>> {
+    let reservation_file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .mode(0o600)
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(reservation_dir.join(format!("X{number}")))?;
+
+    if unsafe { libc::flock(reservation_file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        let err = io::Error::last_os_error();
+        return match err.raw_os_error() {
+            Some(libc::EWOULDBLOCK) => Ok(None),
+            _ => Err(err),
+        };
+    }
+
+    Ok(Some(DisplayReservation {
+        _reservation_file: reservation_file,
+    }))
 }
 
-static X_HAS_STARTED: Lazy<AtomicBool> = Lazy::new(|| AtomicBool::new(false));
+/// Whether the X server that created the lock file at `lock_path` is still running
+///
+/// The lock file contains the PID of the X server padded to 10 characters. A lock file without a
+/// PID is still being written by an X server that is starting, so it counts as running.
+fn display_lock_is_active(lock_path: &Path) -> bool {
+    let content = match fs::read_to_string(lock_path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return false,
+        Err(_) => return true,
+    };
 
-#[allow(dead_code)]
-fn handle_sigusr1(_: i32) {
-    X_HAS_STARTED.store(true, std::sync::atomic::Ordering::SeqCst);
+    let Some(pid) = content
+        .trim()
+        .parse::<libc::pid_t>()
+        .ok()
+        .filter(|pid| *pid > 0)
+    else {
+        return true;
+    };
 
-    unsafe {
-        signal(SIGUSR1, handle_sigusr1 as usize);
+    // A process that cannot be signaled by us still exists
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        return true;
     }
+
+    io::Error::last_os_error().raw_os_error() != Some(libc::ESRCH)
 }
 
-pub fn setup_x(
-    process_env: &mut EnvironmentContainer,
-    user_info: &AuthUserInfo,
-    config: &Config,
-) -> Result<LemursChild, XSetupError> {
-    use std::os::unix::process::CommandExt;
+/// Remove the file at `path` that was left behind by an X server that is no longer running
+fn remove_stale_display_file(path: &Path) {
+    match remove_file(path) {
+        Ok(()) => info!("Removed stale `{path}`", path = path.display()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => warn!(
+            "Failed to remove stale `{path}`. Reason: {err}",
+            path = path.display()
+        ),
+    }
+}
 
-    info!("Start setup of X server");
+/// Find and reserve the first display that is not used within `tmp_dir`
+///
+/// A display is in use when another instance of Lemurs reserved it within `reservation_dir`, when
+/// the X server in its lock file is still running or when an X server accepts connections on its
+/// socket. The lock file and socket left behind by an X server that is no longer running are
+/// removed.
+fn find_free_display_in(
+    tmp_dir: &Path,
+    reservation_dir: &Path,
+) -> Option<(u32, DisplayReservation)> {
+    (0..=MAX_DISPLAY_NUMBER).find_map(|number| {
+        let lock_path = tmp_dir.join(format!(".X{number}-lock"));
+        let socket_path = tmp_dir.join(format!(".X11-unix/X{number}"));
+
+        let reservation = match reserve_display(reservation_dir, number) {
+            Ok(Some(reservation)) => reservation,
+            Ok(None) => return None,
+            Err(err) => {
+                warn!("Failed to reserve X display `:{number}`. Reason: {err}");
+                return None;
+            }
+        };
+
+        if display_lock_is_active(&lock_path) {
+            return None;
+        }
+
+        // A socket without a running X server refuses connections
+        if x_socket_accepts_connections(&socket_path) {
+            return None;
+        }
+
+        remove_stale_display_file(&lock_path);
+        remove_stale_display_file(&socket_path);
+
+        Some((number, reservation))
+    })
+}
+
+/// Resolve the configured `x11_display` to the value of the `DISPLAY` environment variable
+///
+/// An automatically selected display is returned together with its reservation.
+pub fn resolve_display(
+    x11_display: &str,
+) -> Result<(String, Option<DisplayReservation>), XSetupError> {
+    if x11_display.trim() != AUTO_DISPLAY {
+        return Ok((x11_display.to_string(), None));
+    }
+
+    let reservation_dir = Path::new(DISPLAY_RESERVATION_DIR);
+    if let Err(err) = fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(reservation_dir)
+    {
+        error!("Failed to create the X display reservation directory. Reason: {err}");
+        return Err(XSetupError::NoFreeDisplay);
+    }
+
+    let (number, reservation) = find_free_display_in(Path::new("/tmp"), reservation_dir)
+        .ok_or(XSetupError::NoFreeDisplay)?;
+
+    info!("Automatically selected X display `:{number}`");
+
+    Ok((format!(":{number}"), Some(reservation)))
+}
+
+/// Get the number of a local X display (e.g. `1` for `:1` or `:1.0`)
+fn display_number(display: &str) -> Option<u32> {
+    let display = display.strip_prefix(':')?;
+    let number = display.split('.').next()?;
+
+    number.parse::<u32>().ok()
+}
+
+/// Get the path of the UNIX socket of an X display (e.g. `:1` or `:1.0`)
+fn display_socket_path(display: &str) -> Option<PathBuf> {
+    display_number(display).map(|number| PathBuf::from(format!("/tmp/.X11-unix/X{number}")))
+}
+
+/// Whether an X server accepts connections on the UNIX socket at `socket_path`
+///
+/// A socket left behind by an X server that crashed still exists, but refuses connections.
+fn x_socket_accepts_connections(socket_path: &Path) -> bool {
+    UnixStream::connect(socket_path).is_ok()
+}
+
+/// Watch the directory that will contain `socket_path` for new files
+///
+/// The X server creates the socket directory itself when it does not exist yet. In that case, its
+/// parent is watched until the socket directory appears.
+struct XSocketWatcher {
+    inotify: Inotify,
+    socket_dir: PathBuf,
+    watches_socket_dir: bool,
+}
+
+impl XSocketWatcher {
+    fn new(socket_path: &Path) -> io::Result<Self> {
+        let socket_dir = socket_path.parent().unwrap_or(Path::new("/")).to_path_buf();
+        let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)?;
+
+        if let Some(parent) = socket_dir.parent() {
+            inotify.add_watch(parent, AddWatchFlags::IN_CREATE)?;
+        }
+
+        let mut watcher = Self {
+            inotify,
+            socket_dir,
+            watches_socket_dir: false,
+        };
+        watcher.watch_socket_dir();
+
+        Ok(watcher)
+    }
+
+    /// Start watching the socket directory once it exists
+    fn watch_socket_dir(&mut self) {
+        if !self.watches_socket_dir {
+            self.watches_socket_dir = self
+                .inotify
+                .add_watch(&self.socket_dir, AddWatchFlags::IN_CREATE)
+                .is_ok();
+        }
+    }
+}
+
+impl Drop for XSocketWatcher {
+    fn drop(&mut self) {
+        let _ = nix::unistd::close(self.inotify.as_raw_fd());
+    }
+}
+
+/// Wait until the X server started by `child` accepts connections on its UNIX socket
+///
+/// This is used for X servers that are not direct children of Lemurs and thus cannot notify us
+/// with SIGUSR1. The socket directory is watched with inotify. Since the socket is created just
+/// before the X server starts listening on it, and the X server may exit, this is also checked
+/// every `XSTART_CHECK_INTERVAL_MILLIS`.
+pub fn wait_for_x_socket(
+    display: &str,
+    child: &mut LemursChild,
+    timeout_secs: u16,
+) -> Result<(), XSetupError> {
+    const INOTIFY_TOKEN: Token = Token(0);
+
+    let socket_path = display_socket_path(display).ok_or(XSetupError::DisplayEnvVar)?;
+
+    info!(
+        "Waiting for X socket at `{socket_path}`",
+        socket_path = socket_path.display()
+    );
+
+    let mut poll = Poll::new().map_err(|err| {
+        error!("Failed to wait for the X socket. Reason: {err}");
+        XSetupError::ReadinessWait
+    })?;
+
+    let watcher = XSocketWatcher::new(&socket_path).and_then(|watcher| {
+        poll.registry().register(
+            &mut SourceFd(&watcher.inotify.as_raw_fd()),
+            INOTIFY_TOKEN,
+            Interest::READABLE,
+        )?;
+        Ok(watcher)
+    });
+    let mut watcher = match watcher {
+        Ok(watcher) => Some(watcher),
+        Err(err) => {
+            warn!("Failed to watch for the X socket. Only checking periodically. Reason: {err}");
+            None
+        }
+    };
+    let mut events = Events::with_capacity(16);
+
+    let start_time = time::Instant::now();
+    let timeout = (timeout_secs != 0).then(|| time::Duration::from_secs(timeout_secs.into()));
+
+    loop {
+        if x_socket_accepts_connections(&socket_path) {
+            break;
+        }
+
+        if let Some(status) = child.try_wait().unwrap_or(None) {
+            error!("X server died before it accepted connections. Status code: {status}.");
+
+            return Err(XSetupError::XServerPrematureExit);
+        }
+
+        // The caller kills the X server when it does not start
+        if timeout.is_some_and(|timeout| start_time.elapsed() > timeout) {
+            return Err(XSetupError::XServerTimeout);
+        }
 
-    let display_value = env::var("DISPLAY").map_err(|_| XSetupError::DisplayEnvVar)?;
-    let vtnr_value = env::var("XDG_VTNR").map_err(|_| XSetupError::VTNREnvVar)?;
+        let poll_result = poll.poll(
+            &mut events,
+            Some(time::Duration::from_millis(XSTART_CHECK_INTERVAL_MILLIS)),
+        );
 
-    // Setup xauth
-    let xauth_dir = PathBuf::from(env::var("HOME").map_err(|_| XSetupError::HomeEnvVar)?);
-    let xauth_path = xauth_dir.join(".Xauthority");
+        if let Err(err) = poll_result {
+            if err.kind() != io::ErrorKind::Interrupted {
+                error!("Failed to wait for the X socket. Reason: {err}");
+                return Err(XSetupError::ReadinessWait);
+            }
+        }
+
+        // Drain the inotify events. We only use them as a wake-up.
+        if let Some(watcher) = watcher.as_mut() {
+            if !events.is_empty() {
+                let _ = watcher.inotify.read_events();
+            }
+            watcher.watch_socket_dir();
+        }
+    }
+
+    info!(
+        "It took X server {start_ms}ms to accept connections on its socket",
+        start_ms = start_time.elapsed().as_millis()
+    );
+
+    Ok(())
+}
+
+/// An X server together with the authorization file it was started with
+pub struct XServer {
+    pub process: LemursChild,
+    auth_path: PathBuf,
+}
+
+impl XServer {
+    /// Remove the authorization file of the X server. This should be done once the X server has
+    /// stopped.
+    pub fn remove_auth_file(&self) {
+        remove_server_auth_file(&self.auth_path);
+    }
+
+    /// Kill and reap the X server and remove its authorization file. This is used when the session
+    /// fails to start after the X server was started.
+    pub fn kill(&mut self) {
+        if let Err(err) = self.process.kill() {
+            error!("Failed to kill X server. Reason: {err}");
+        }
+
+        if let Err(err) = self.process.wait() {
+            error!("Failed to wait for X server. Reason: {err}");
+        }
+
+        self.remove_auth_file();
+    }
+}
+
+/// Remove the authorization file of an X server that has stopped
+pub fn remove_server_auth_file(auth_path: &Path) {
+    match remove_file(auth_path) {
+        Ok(()) => info!(
+            "Removed X server authorization file `{}`",
+            auth_path.display()
+        ),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+        Err(err) => warn!(
+            "Failed to remove X server authorization file `{}`. Reason: {err}",
+            auth_path.display()
+        ),
+    }
+}
+
+/// Write the `.Xauthority` file of the user with `cookie` and point `XAUTHORITY` to it
+fn setup_client_xauth(
+    process_env: &mut EnvironmentContainer,
+    user_info: &AuthUserInfo,
+    config: &Config,
+    display_number: u32,
+    cookie: &Cookie,
+) -> Result<(), XSetupError> {
+    let xauth_dir = if config.x11.xauthority_in_runtime_dir {
+        process_env
+            .get("XDG_RUNTIME_DIR")
+            .ok_or(XSetupError::RuntimeDirEnvVar)?
+    } else {
+        process_env.get("HOME").ok_or(XSetupError::HomeEnvVar)?
+    };
+    let xauth_path = PathBuf::from(xauth_dir).join(".Xauthority");
 
     info!(
         "Filling `.Xauthority` file at `{xauth_path}`",
         xauth_path = xauth_path.display()
     );
 
-    // Make sure that we are generating a new file. This is necessary sometimes, since there may be
-    // a `root` permission `.Xauthority` file there.
-    let _ = remove_file(&xauth_path);
-
-    Command::new(&config.system_shell)
-        .arg("-c")
-        .arg(format!(
-            "{} add {} . {}",
-            &config.x11.xauth_path,
-            display_value,
-            mcookie()
-        ))
-        .uid(user_info.uid)
-        .gid(user_info.primary_gid)
-        .stdout(Stdio::null()) // TODO: Maybe this should be logged or something?
-        .stderr(Stdio::null()) // TODO: Maybe this should be logged or something?
-        .status()
-        .map_err(|err| {
-            error!(
-                "Failed to fill Xauthority file with `xauth`. Reason: {}",
-                err
-            );
-            XSetupError::FillingXAuth
-        })?;
+    write_xauthority(
+        &xauth_path,
+        display_number,
+        cookie,
+        Some((user_info.uid, user_info.primary_gid)),
+    )
+    .map_err(|err| {
+        error!("Failed to fill Xauthority file. Reason: {err}");
+        XSetupError::FillingXAuth
+    })?;
 
     let xauth_path = xauth_path.to_str().ok_or(XSetupError::InvalidUTF8Path)?;
     process_env.set("XAUTHORITY", xauth_path);
 
+    Ok(())
+}
+
+/// Set up the authorization for an Xwayland server that is started as the logged in user
+///
+/// The client gets its `.Xauthority` file in the same way as with `setup_x`. The returned server
+/// authorization file is placed in the runtime directory of the user, so that Xwayland can read
+/// it, and should be passed to Xwayland with `-auth`.
+pub fn setup_xwayland_auth(
+    process_env: &mut EnvironmentContainer,
+    user_info: &AuthUserInfo,
+    config: &Config,
+) -> Result<PathBuf, XSetupError> {
+    let display_value = process_env
+        .get("DISPLAY")
+        .ok_or(XSetupError::DisplayEnvVar)?;
+    let display_number = display_number(display_value).ok_or(XSetupError::InvalidDisplay)?;
+    let server_auth_path = PathBuf::from(
+        process_env
+            .get("XDG_RUNTIME_DIR")
+            .ok_or(XSetupError::RuntimeDirEnvVar)?,
+    )
+    .join(format!("lemurs-X{display_number}.Xauthority"));
+
+    let cookie = Cookie::generate().map_err(|err| {
+        error!("Failed to generate X authorization cookie. Reason: {err}");
+        XSetupError::FillingXAuth
+    })?;
+
+    setup_client_xauth(process_env, user_info, config, display_number, &cookie)?;
+
+    write_xauthority(
+        &server_auth_path,
+        display_number,
+        &cookie,
+        Some((user_info.uid, user_info.primary_gid)),
+    )
+    .map_err(|err| {
+        error!("Failed to write Xwayland authorization file. Reason: {err}");
+        XSetupError::ServerAuth
+    })?;
+
+    Ok(server_auth_path)
+}
+
+pub fn setup_x(
+    process_env: &mut EnvironmentContainer,
+    user_info: &AuthUserInfo,
+    config: &Config,
+) -> Result<XServer, XSetupError> {
+    info!("Start setup of X server");
+
+    let display_value = process_env
+        .get("DISPLAY")
+        .ok_or(XSetupError::DisplayEnvVar)?
+        .to_string();
+    let vtnr_value = process_env
+        .get("XDG_VTNR")
+        .ok_or(XSetupError::VTNREnvVar)?
+        .to_string();
+
+    let display_number = display_number(&display_value).ok_or(XSetupError::InvalidDisplay)?;
+
+    let cookie = Cookie::generate().map_err(|err| {
+        error!("Failed to generate X authorization cookie. Reason: {err}");
+        XSetupError::FillingXAuth
+    })?;
+
+    setup_client_xauth(process_env, user_info, config, display_number, &cookie)?;
+
+    // A rootless X server needs to be able to read its authorization file. Therefore, it is
+    // placed in the runtime directory of the user instead.
+    let rootless_runtime_dir = if config.x11.xserver_rootless {
+        match process_env.get("XDG_RUNTIME_DIR") {
+            Some(runtime_dir) => Some(runtime_dir.to_string()),
+            None => {
+                warn!("A rootless X server requires `XDG_RUNTIME_DIR` to be set. Falling back to starting the X server as root");
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    // Setup the server authorization file. This makes the X server enforce the cookie.
+    let (server_auth_path, server_auth_owner) = match &rootless_runtime_dir {
+        Some(runtime_dir) => (
+            PathBuf::from(runtime_dir).join(format!("lemurs-X{display_number}.Xauthority")),
+            Some((user_info.uid, user_info.primary_gid)),
+        ),
+        None => {
+            let server_auth_dir = Path::new(&config.x11.xserver_auth_dir);
+            fs::DirBuilder::new()
+                .recursive(true)
+                .mode(0o700)
+                .create(server_auth_dir)
+                .map_err(|err| {
+                    error!("Failed to create X server authorization directory. Reason: {err}");
+                    XSetupError::ServerAuth
+                })?;
+
+            (
+                server_auth_dir.join(format!("X{display_number}.Xauthority")),
+                None,
+            )
+        }
+    };
+
+    write_xauthority(
+        &server_auth_path,
+        display_number,
+        &cookie,
+        server_auth_owner,
+    )
+    .map_err(|err| {
+        error!("Failed to write X server authorization file. Reason: {err}");
+        XSetupError::ServerAuth
+    })?;
+
+    match start_x_server(
+        process_env,
+        user_info,
+        config,
+        &display_value,
+        vtnr_value,
+        &server_auth_path,
+        rootless_runtime_dir.is_some(),
+    ) {
+        Ok(process) => Ok(XServer {
+            process,
+            auth_path: server_auth_path,
+        }),
+        Err(err) => {
+            remove_server_auth_file(&server_auth_path);
+            Err(err)
+        }
+    }
+}
+
+/// Start the X server with the authorization file at `server_auth_path`
+///
+/// If `rootless` is set, the X server is first attempted to be started as the logged in user.
+fn start_x_server(
+    process_env: &EnvironmentContainer,
+    user_info: &AuthUserInfo,
+    config: &Config,
+    display_value: &str,
+    vtnr_value: String,
+    server_auth_path: &Path,
+    rootless: bool,
+) -> Result<LemursChild, XSetupError> {
+    let tty_path = format!("/dev/tty{vtnr_value}");
     let doubledigit_vtnr = if vtnr_value.len() == 1 {
         format!("0{vtnr_value}")
     } else {
         vtnr_value
     };
 
-    // Here we explicitely ignore the first USR defined signal. Xorg looks at whether this signal
-    // is ignored or not. If it is ignored, it will send that signal to the parent when it ready to
-    // receive connections. This is also how xinit does it.
-    //
-    // After we spawn the Xorg process, we need to make sure to quickly re-enable this signal as we
-    // need to listen to the signal by Xorg.
-    unsafe {
-        libc::signal(SIGUSR1, SIG_IGN);
-    }
+    let x_server_command = || {
+        let mut command = Command::new(&config.x11.xserver_path);
+        process_env.apply(&mut command);
 
-    let mut child = Command::new(&config.system_shell);
+        // The arguments are passed directly to the X server, so they don't need any escaping
+        command
+            .arg(display_value)
+            .arg(format!("vt{doubledigit_vtnr}"))
+            .arg("-auth")
+            .arg(server_auth_path)
+            .args(&config.x11.xserver_args);
+
+        command
+    };
 
     let log_path = config
         .do_log
         .then_some(Path::new(&config.x11.xserver_log_path));
 
-    child.arg("-c").arg(format!(
-        "{} {display_value} vt{doubledigit_vtnr}",
-        &config.x11.xserver_path
-    ));
+    if rootless {
+        match start_rootless_x_server(
+            x_server_command(),
+            &tty_path,
+            display_value,
+            user_info,
+            log_path,
+            config.x11.xserver_timeout_secs,
+        ) {
+            Ok(child) => {
+                info!(
+                    "X server is running rootless as user `{username}`",
+                    username = user_info.username
+                );
+                return Ok(child);
+            }
+            Err(err) => {
+                warn!("Failed to start a rootless X server. Falling back to starting the X server as root. Reason: {err}");
+            }
+        }
+    }
+
+    // Xorg looks at whether the first USR defined signal is ignored or not. If it is ignored, it
+    // will send that signal to the parent when it ready to receive connections. This is also how
+    // xinit does it.
+    let mut child = x_server_command();
+    ignore_sigusr1_in_child(&mut child);
+
+    // This needs to listen before the X server is spawned, so that no signal is missed.
+    let mut ready_watcher = XReadyWatcher::new().map_err(|err| {
+        error!("Failed to listen for the X server to start. Reason: {err}");
+        XSetupError::ReadinessWait
+    })?;
+
+    info!("Starting X server as root with `{child:?}`");
 
     let mut child = LemursChild::spawn(child, log_path).map_err(|err| {
         error!("Failed to start X server. Reason: {}", err);
         XSetupError::XServerStart
     })?;
 
-    // See note above
-    unsafe {
-        libc::signal(SIGUSR1, SIG_DFL);
-        signal(SIGUSR1, handle_sigusr1 as usize);
-    }
-
     // Wait for XServer to boot-up
-    let start_time = time::SystemTime::now();
-    loop {
-        if config.x11.xserver_timeout_secs == 0
-            || start_time.elapsed().map_or(false, |t| {
-                t.as_secs() > config.x11.xserver_timeout_secs.into()
-            })
-        {
-            break;
+    let start_time = time::Instant::now();
+    if let Err(err) = ready_watcher.wait(&mut child, config.x11.xserver_timeout_secs) {
+        if !matches!(err, XSetupError::XServerPrematureExit) {
+            kill_and_reap(&mut child, "Xorg");
         }
 
-        // This will be set by the `handle_sigusr1` signal handler.
-        if X_HAS_STARTED.load(std::sync::atomic::Ordering::SeqCst) {
-            break;
-        }
+        return Err(err);
+    }
 
-        if let Some(status) = child.try_wait().unwrap_or(None) {
-            error!("X server died before signaling it was ready to received connections. Status code: {status}.");
+    drop(ready_watcher);
 
-            return Err(XSetupError::XServerPrematureExit);
-        }
+    info!(
+        "It took X server {start_ms}ms to start",
+        start_ms = start_time.elapsed().as_millis()
+    );
+
+    info!("X server is running as root");
+
+    Ok(child)
+}
+
+/// Start the X server as the logged in user
+///
+/// The X server runs on the VT of the session, which is given to it as standard input. Since an
+/// unprivileged X server cannot signal us with SIGUSR1, we wait for its socket instead.
+fn start_rootless_x_server(
+    command: Command,
+    tty_path: &str,
+    display: &str,
+    user_info: &AuthUserInfo,
+    log_path: Option<&Path>,
+    timeout_secs: u16,
+) -> Result<LemursChild, XSetupError> {
+    let tty = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(tty_path)
+        .map_err(|err| {
+            error!("Failed to open `{tty_path}` for the rootless X server. Reason: {err}");
+            XSetupError::XServerStart
+        })?;
+
+    let command = lower_command_permissions_to_user(command, user_info);
+
+    info!("Starting rootless X server with `{command:?}`");
+
+    let mut child =
+        LemursChild::spawn_with_stdin(command, tty.into(), log_path).map_err(|err| {
+            error!("Failed to start rootless X server. Reason: {err}");
+            XSetupError::XServerStart
+        })?;
+
+    if let Err(err) = wait_for_x_socket(display, &mut child, timeout_secs) {
+        kill_and_reap(&mut child, "rootless X server");
+        return Err(err);
+    }
 
-        thread::sleep(time::Duration::from_millis(XSTART_CHECK_INTERVAL_MILLIS));
+    Ok(child)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        find_free_display_in, wait_for_x_socket, x_socket_accepts_connections, LemursChild,
+        XSetupError,
+    };
+
+    use std::fs;
+    use std::os::unix::net::UnixListener;
+    use std::path::PathBuf;
+    use std::process::Command;
+    use std::time;
+
+    /// Create an empty temporary directory and reservation directory for the display tests
+    fn display_test_dirs(name: &str) -> (PathBuf, PathBuf) {
+        let tmp_dir = std::env::temp_dir().join(format!("lemurs-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&tmp_dir);
+        fs::create_dir_all(tmp_dir.join(".X11-unix")).unwrap();
+        fs::create_dir_all(tmp_dir.join("reservations")).unwrap();
+
+        let reservation_dir = tmp_dir.join("reservations");
+        (tmp_dir, reservation_dir)
     }
 
-    // If the value is still `false`, this means we have time-ed out and Xorg is not running.
-    if !X_HAS_STARTED.load(std::sync::atomic::Ordering::SeqCst) {
-        child.kill().unwrap_or_else(|err| {
-            error!("Failed to kill Xorg after it timed out. Reason: {err}");
-        });
-        return Err(XSetupError::XServerTimeout);
+    #[test]
+    fn free_display_skips_running_x_servers() {
+        let (tmp_dir, reservation_dir) = display_test_dirs("x-running-test");
+
+        // Display 0 is locked by a running process (ourselves)
+        fs::write(
+            tmp_dir.join(".X0-lock"),
+            format!("{:>10}\n", std::process::id()),
+        )
+        .unwrap();
+
+        // Display 1 is locked by a server that has not written its PID yet
+        fs::write(tmp_dir.join(".X1-lock"), "").unwrap();
+
+        // Display 2 has a socket that accepts connections
+        let _listener = UnixListener::bind(tmp_dir.join(".X11-unix/X2")).unwrap();
+
+        let (number, _reservation) = find_free_display_in(&tmp_dir, &reservation_dir).unwrap();
+        assert_eq!(number, 3);
+        assert!(tmp_dir.join(".X0-lock").exists());
+        assert!(tmp_dir.join(".X1-lock").exists());
+        assert!(tmp_dir.join(".X11-unix/X2").exists());
+
+        fs::remove_dir_all(&tmp_dir).unwrap();
     }
 
-    if let Ok(x_server_start_time) = start_time.elapsed() {
-        info!(
-            "It took X server {start_ms}ms to start",
-            start_ms = x_server_start_time.as_millis()
-        );
+    #[test]
+    fn free_display_removes_stale_locks_and_sockets() {
+        let (tmp_dir, reservation_dir) = display_test_dirs("x-stale-test");
+
+        // No process can have the highest PID
+        fs::write(
+            tmp_dir.join(".X0-lock"),
+            format!("{:>10}\n", libc::pid_t::MAX),
+        )
+        .unwrap();
+        fs::write(tmp_dir.join(".X11-unix/X0"), "").unwrap();
+
+        let (number, _reservation) = find_free_display_in(&tmp_dir, &reservation_dir).unwrap();
+        assert_eq!(number, 0);
+        assert!(!tmp_dir.join(".X0-lock").exists());
+        assert!(!tmp_dir.join(".X11-unix/X0").exists());
+
+        fs::remove_dir_all(&tmp_dir).unwrap();
     }
 
-    X_HAS_STARTED.store(false, std::sync::atomic::Ordering::SeqCst);
+    #[test]
+    fn reserved_display_is_not_selected_again() {
+        let (tmp_dir, reservation_dir) = display_test_dirs("x-reservation-test");
 
-    info!("X server is running");
+        let (number, reservation) = find_free_display_in(&tmp_dir, &reservation_dir).unwrap();
+        assert_eq!(number, 0);
 
-    Ok(child)
+        // The X server can still create its own lock file
+        assert!(!tmp_dir.join(".X0-lock").exists());
+
+        let (next_number, _next_reservation) =
+            find_free_display_in(&tmp_dir, &reservation_dir).unwrap();
+        assert_eq!(next_number, 1);
+
+        drop(reservation);
+        let (number, _reservation) = find_free_display_in(&tmp_dir, &reservation_dir).unwrap();
+        assert_eq!(number, 0);
+
+        fs::remove_dir_all(&tmp_dir).unwrap();
+    }
+
+    #[test]
+    fn stale_x_socket_is_not_ready() {
+        let socket_path =
+            std::env::temp_dir().join(format!("lemurs-x-socket-test-{}", std::process::id()));
+        let _ = fs::remove_file(&socket_path);
+
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        assert!(x_socket_accepts_connections(&socket_path));
+
+        // The socket file stays behind once nothing listens on it anymore
+        drop(listener);
+        assert!(socket_path.exists());
+        assert!(!x_socket_accepts_connections(&socket_path));
+
+        fs::remove_file(&socket_path).unwrap();
+    }
+
+    #[test]
+    fn exited_x_server_stops_the_wait_for_its_socket() {
+        let mut child = LemursChild::spawn(Command::new("true"), None).unwrap();
+
+        let start_time = time::Instant::now();
+        assert!(matches!(
+            wait_for_x_socket(":65535", &mut child, 0),
+            Err(XSetupError::XServerPrematureExit)
+        ));
+        assert!(start_time.elapsed() < time::Duration::from_secs(5));
+    }
 }
@@ -0,0 +1,219 @@
+//! This module implements waiting for an X server to signal that it accepts connections.
+//!
+//! When the X server starts with SIGUSR1 ignored, it sends SIGUSR1 to its parent once it is ready.
+//! While waiting, a signal handler forwards SIGUSR1 and SIGCHLD together with the PID of the
+//! sender over a pipe, which is polled with `mio`. Only a signal from the X server that is waited
+//! upon is accepted as readiness. The SIGUSR1 handler is kept afterwards, since the X server
+//! signals again every time it resets.
+
+use std::io::{self, Read};
+use std::mem;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use log::error;
+use mio::unix::pipe::{self, Receiver, Sender};
+use mio::{Events, Interest, Poll, Token};
+
+use super::wait_with_log::LemursChild;
+use super::x::XSetupError;
+
+/// The write end of the pipe of the active watcher or `-1` if there is none
+static SIGNAL_PIPE: AtomicI32 = AtomicI32::new(-1);
+/// Only one watcher can own the signal handlers at a time
+static WATCHER_LOCK: Mutex<()> = Mutex::new(());
+
+const FORWARDED_SIGNALS: [libc::c_int; 2] = [libc::SIGUSR1, libc::SIGCHLD];
+
+/// A message consists of the signal number and the PID of the sender
+const MESSAGE_SIZE: usize = 8;
+
+extern "C" fn forward_signal(
+    signo: libc::c_int,
+    info: *mut libc::siginfo_t,
+    _context: *mut libc::c_void,
+) {
+    let fd = SIGNAL_PIPE.load(Ordering::SeqCst);
+    if fd < 0 {
+        return;
+    }
+
+    // Only async-signal-safe functions may be used here. The `errno` needs to be preserved for the
+    // code that was interrupted.
+    unsafe {
+        let saved_errno = *libc::__errno_location();
+
+        let pid = (*info).si_pid();
+
+        let mut message = [0u8; MESSAGE_SIZE];
+        message[..4].copy_from_slice(&signo.to_ne_bytes());
+        message[4..].copy_from_slice(&pid.to_ne_bytes());
+
+        // If the pipe is full, the message is dropped. There are already enough wake-ups queued.
+        libc::write(fd, message.as_ptr().cast(), MESSAGE_SIZE);
+
+        *libc::__errno_location() = saved_errno;
+    }
+}
+
+/// Make the X server started by `command` signal its parent once it accepts connections
+///
+/// Xorg checks whether SIGUSR1 is ignored on startup. This is only set for the child, so the
+/// signal disposition of Lemurs itself is never changed.
+pub fn ignore_sigusr1_in_child(command: &mut Command) {
+    unsafe {
+        command.pre_exec(|| {
+            libc::signal(libc::SIGUSR1, libc::SIG_IGN);
+            Ok(())
+        });
+    }
+}
+
+pub struct XReadyWatcher {
+    receiver: Receiver,
+    sender: Sender,
+    previous_actions: Vec<(libc::c_int, libc::sigaction)>,
+    _guard: MutexGuard<'static, ()>,
+}
+
+impl XReadyWatcher {
+    /// Start listening for signals
+    ///
+    /// This should be called before the X server is spawned, so that no signal is missed.
+    pub fn new() -> io::Result<Self> {
+        let guard = WATCHER_LOCK
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        let (sender, receiver) = pipe::new()?;
+
+        let mut watcher = Self {
+            receiver,
+            sender,
+            previous_actions: Vec::with_capacity(FORWARDED_SIGNALS.len()),
+            _guard: guard,
+        };
+
+        SIGNAL_PIPE.store(watcher.sender.as_raw_fd(), Ordering::SeqCst);
+
+        let mut action: libc::sigaction = unsafe { mem::zeroed() };
+        action.sa_sigaction = forward_signal as *const () as libc::sighandler_t;
+        action.sa_flags = libc::SA_SIGINFO | libc::SA_RESTART | libc::SA_NOCLDSTOP;
+        unsafe { libc::sigemptyset(&mut action.sa_mask) };
+
+        for signal in FORWARDED_SIGNALS {
+            let mut previous_action: libc::sigaction = unsafe { mem::zeroed() };
+            if unsafe { libc::sigaction(signal, &action, &mut previous_action) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            watcher.previous_actions.push((signal, previous_action));
+        }
+
+        Ok(watcher)
+    }
+
+    /// Read all pending messages as pairs of signal number and sender PID
+    fn read_messages(&mut self) -> io::Result<Vec<(libc::c_int, libc::pid_t)>> {
+        let mut messages = Vec::new();
+        let mut message = [0u8; MESSAGE_SIZE];
+
+        loop {
+            match self.receiver.read_exact(&mut message) {
+                Ok(()) => {
+                    let mut signo = [0u8; 4];
+                    let mut pid = [0u8; 4];
+                    signo.copy_from_slice(&message[..4]);
+                    pid.copy_from_slice(&message[4..]);
+
+                    messages.push((
+                        libc::c_int::from_ne_bytes(signo),
+                        libc::pid_t::from_ne_bytes(pid),
+                    ));
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(messages),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Wait for the X server `child` to signal that it accepts connections
+    ///
+    /// A `timeout_secs` of 0 waits indefinitely.
+    pub fn wait(&mut self, child: &mut LemursChild, timeout_secs: u16) -> Result<(), XSetupError> {
+        const PIPE_TOKEN: Token = Token(0);
+
+        let wait_error = |err: io::Error| {
+            error!("Failed to wait for the X server to start. Reason: {err}");
+            XSetupError::ReadinessWait
+        };
+
+        let mut poll = Poll::new().map_err(wait_error)?;
+        poll.registry()
+            .register(&mut self.receiver, PIPE_TOKEN, Interest::READABLE)
+            .map_err(wait_error)?;
+        let mut events = Events::with_capacity(4);
+
+        let pid = child.id() as libc::pid_t;
+        let deadline =
+            (timeout_secs != 0).then(|| Instant::now() + Duration::from_secs(timeout_secs.into()));
+
+        loop {
+            let timeout = match deadline {
+                None => None,
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(remaining) => Some(remaining),
+                    None => return Err(XSetupError::XServerTimeout),
+                },
+            };
+
+            if let Err(err) = poll.poll(&mut events, timeout) {
+                if err.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+
+                return Err(wait_error(err));
+            }
+
+            for (signo, sender) in self.read_messages().map_err(wait_error)? {
+                if signo == libc::SIGUSR1 && sender == pid {
+                    return Ok(());
+                }
+
+                // SIGCHLD signals can be merged, so the sender is not reliable here
+                if signo == libc::SIGCHLD {
+                    if let Some(status) = child.try_wait().unwrap_or(None) {
+                        error!("X server died before signaling it was ready to received connections. Status code: {status}.");
+
+                        return Err(XSetupError::XServerPrematureExit);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Drop for XReadyWatcher {
+    fn drop(&mut self) {
+        for (signal, previous_action) in self.previous_actions.drain(..) {
+            // The X server sends SIGUSR1 again every time it resets (e.g. when the last client
+            // disconnects), for which the default action would terminate Lemurs. The handler
+            // therefore stays installed and drops the signal once there is no pipe. Unlike an
+            // ignored signal, a handler is not inherited by the processes Lemurs spawns.
+            if signal == libc::SIGUSR1 {
+                continue;
+            }
+
+            unsafe {
+                libc::sigaction(signal, &previous_action, std::ptr::null_mut());
+            }
+        }
+
+        SIGNAL_PIPE.store(-1, Ordering::SeqCst);
+    }
+}
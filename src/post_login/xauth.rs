@@ -0,0 +1,172 @@
+//! This module implements the writing of Xauthority files without relying on the `xauth` binary.
+//!
+//! An Xauthority file is a sequence of entries. Every entry consists of a big-endian `u16` family
+//! followed by the address, display number, authorization name and authorization data. Each of
+//! those is a big-endian `u16` length followed by that many bytes.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+use nix::unistd::{Gid, Uid};
+
+/// Matches any address. This makes the entry independent of the hostname.
+const FAMILY_WILD: u16 = 0xFFFF;
+const MIT_MAGIC_COOKIE: &str = "MIT-MAGIC-COOKIE-1";
+const COOKIE_LENGTH: usize = 16;
+
+/// A MIT-MAGIC-COOKIE-1 authorization cookie
+pub struct Cookie([u8; COOKIE_LENGTH]);
+
+impl Cookie {
+    /// Generate a new cookie from the operating system's random number generator
+    pub fn generate() -> Result<Self, getrandom::Error> {
+        let mut cookie = [0u8; COOKIE_LENGTH];
+        getrandom::getrandom(&mut cookie)?;
+        Ok(Self(cookie))
+    }
+}
+
+fn push_field(buffer: &mut Vec<u8>, field: &[u8]) {
+    // All fields are far smaller than `u16::MAX`
+    buffer.extend_from_slice(&(field.len() as u16).to_be_bytes());
+    buffer.extend_from_slice(field);
+}
+
+/// Encode the Xauthority entry for a display number (e.g. `1` for `:1`)
+fn encode_entry(display_number: u32, cookie: &Cookie) -> Vec<u8> {
+    let mut buffer = Vec::new();
+
+    buffer.extend_from_slice(&FAMILY_WILD.to_be_bytes());
+    push_field(&mut buffer, &[]);
+    push_field(&mut buffer, display_number.to_string().as_bytes());
+    push_field(&mut buffer, MIT_MAGIC_COOKIE.as_bytes());
+    push_field(&mut buffer, &cookie.0);
+
+    buffer
+}
+
+/// Atomically write an Xauthority file containing only the entry for the given display
+///
+/// The file is created with `0600` permissions and, if given, owned by `owner`. The content is
+/// first written to a temporary file in the same directory, which is then moved into place.
+pub fn write_xauthority(
+    path: &Path,
+    display_number: u32,
+    cookie: &Cookie,
+    owner: Option<(libc::uid_t, libc::gid_t)>,
+) -> io::Result<()> {
+    let Some(file_name) = path.file_name() else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Xauthority path has no file name",
+        ));
+    };
+
+    let mut tmp_file_name = file_name.to_os_string();
+    tmp_file_name.push(format!(".lemurs-{}", std::process::id()));
+    let tmp_path = path.with_file_name(tmp_file_name);
+
+    // Remove leftovers from earlier attempts. `create_new` below refuses to follow symbolic links.
+    let _ = fs::remove_file(&tmp_path);
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&tmp_path)?;
+
+    let write_result = (|| {
+        file.write_all(&encode_entry(display_number, cookie))?;
+
+        if let Some((uid, gid)) = owner {
+            nix::unistd::fchown(
+                file.as_raw_fd(),
+                Some(Uid::from_raw(uid)),
+                Some(Gid::from_raw(gid)),
+            )?;
+        }
+
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)
+    })();
+
+    if write_result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+
+    write_result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode_entry, write_xauthority, Cookie};
+
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::PathBuf;
+
+    const COOKIE: Cookie = Cookie([0xAB; 16]);
+
+    fn xauthority_test_path(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("lemurs-xauth-test-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        dir.join(".Xauthority")
+    }
+
+    #[test]
+    fn entry_matches_any_address() {
+        let entry = encode_entry(12, &COOKIE);
+
+        // The wildcard family is followed by an empty address
+        assert_eq!(entry[..4], [0xFF, 0xFF, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn entry_holds_display_number_as_text() {
+        let entry = encode_entry(12, &COOKIE);
+
+        assert_eq!(entry[4..10], [0x00, 0x02, b'1', b'2', 0x00, 0x12]);
+    }
+
+    #[test]
+    fn entry_holds_magic_cookie() {
+        let entry = encode_entry(12, &COOKIE);
+
+        let mut expected = b"MIT-MAGIC-COOKIE-1".to_vec();
+        expected.extend_from_slice(&[0x00, 0x10]);
+        expected.extend_from_slice(&[0xAB; 16]);
+
+        assert_eq!(entry[10..], expected);
+    }
+
+    #[test]
+    fn xauthority_is_private() {
+        let path = xauthority_test_path("private");
+        write_xauthority(&path, 1, &COOKIE, None).unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn xauthority_replaces_previous_entries() {
+        let path = xauthority_test_path("replace");
+        fs::write(&path, b"previous entries").unwrap();
+
+        write_xauthority(&path, 1, &COOKIE, None).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), encode_entry(1, &COOKIE));
+
+        // No temporary file is left behind
+        assert_eq!(fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,228 @@
+//! This module implements the loading of themes.
+//!
+//! A theme is a partial configuration with the colors and modifiers of the widgets together with a
+//! palette. Any other setting within a theme file is ignored. The theme is merged over the default
+//! configuration, after which the configuration file is merged over it. This way, the
+//! configuration file can adjust any part of the theme.
+
+use std::fs::read_to_string;
+use std::path::Path;
+
+use log::{info, warn};
+use toml::value::{Table, Value};
+
+use crate::config::{PartialConfig, ThemeConfig, Variables};
+
+/// The themes that are bundled with Lemurs
+const PRESETS: [(&str, &str); 3] = [
+    ("nord", include_str!("../extra/themes/nord.toml")),
+    ("gruvbox", include_str!("../extra/themes/gruvbox.toml")),
+    ("dracula", include_str!("../extra/themes/dracula.toml")),
+];
+
+/// Whether the setting `key` within a theme file is a color or modifier
+fn is_style_key(key: &str) -> bool {
+    let key = key.strip_suffix("_focused").unwrap_or(key);
+
+    key == "color"
+        || key.ends_with("_color")
+        || key == "modifiers"
+        || key.ends_with("_modifiers")
+        || key == "line_colors"
+        || key == "console_palette"
+}
+
+/// Remove all settings from a theme file that are not a color, modifier or the palette
+///
+/// Lists of tables (e.g. the power control entries) also contain commands and are removed as a
+/// whole.
+fn retain_style_keys(table: &mut Table, path: &str) {
+    let keys: Vec<String> = table.keys().cloned().collect();
+
+    for key in keys {
+        let field = if path.is_empty() {
+            key.clone()
+        } else {
+            format!("{path}.{key}")
+        };
+
+        let keep = match table.get_mut(&key) {
+            Some(Value::Table(_)) if field == "theme.palette" => true,
+            Some(Value::Table(inner)) => {
+                retain_style_keys(inner, &field);
+                true
+            }
+            _ => is_style_key(&key),
+        };
+
+        if !keep {
+            warn!("Ignoring `{field}` within the theme. Themes can only set colors and modifiers.");
+            table.remove(&key);
+        }
+    }
+}
+
+/// Read the theme with the configured name from the themes directory or the bundled themes
+fn read_theme(
+    theme: &ThemeConfig,
+    variables: Option<&Variables>,
+) -> Result<Option<PartialConfig>, Box<dyn std::error::Error>> {
+    let name = theme.name.trim();
+
+    if name.is_empty() {
+        return Ok(None);
+    }
+
+    if name.contains('/') {
+        return Err(format!("Invalid theme name '{name}'").into());
+    }
+
+    let path = Path::new(&theme.themes_path).join(format!("{name}.toml"));
+    let contents = if path.is_file() {
+        info!("Loading theme from '{}'", path.display());
+        read_to_string(&path)?
+    } else if let Some((_, contents)) = PRESETS.iter().find(|(preset, _)| *preset == name) {
+        info!("Loading bundled theme '{name}'");
+        contents.to_string()
+    } else {
+        return Err(format!("Theme '{name}' does not exist").into());
+    };
+
+    let mut table = toml::from_str::<Table>(&contents)?;
+    retain_style_keys(&mut table, "");
+
+    Ok(Some(PartialConfig::from_table(table, variables)?))
+}
+
+/// Load the configured theme
+///
+/// The result should be merged over the default configuration before the configuration file is
+/// merged in.
+pub fn load_theme(theme: &ThemeConfig, variables: Option<&Variables>) -> Option<PartialConfig> {
+    read_theme(theme, variables).unwrap_or_else(|err| {
+        warn!("Failed to load the theme. Reason: {err}");
+        None
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    /// The configuration of every bundled theme, with `user_config` merged over it
+    fn preset_configs(user_config: &str) -> Vec<(&'static str, Config)> {
+        let user_config = PartialConfig::from_contents(user_config, None).unwrap();
+
+        PRESETS
+            .iter()
+            .map(|(name, _)| {
+                let mut theme_config = Config::default().theme;
+                theme_config.name = name.to_string();
+
+                let theme =
+                    load_theme(&theme_config, None).expect("Bundled theme cannot be loaded");
+                assert!(theme.theme.is_some(), "Theme '{name}' has no palette");
+
+                let mut config = Config::default();
+                config.merge_in_partial(theme);
+                config.merge_in_partial(user_config.clone());
+
+                (*name, config)
+            })
+            .collect()
+    }
+
+    /// Parse `contents` and keep only its style keys
+    fn style_keys(contents: &str) -> Table {
+        let mut table = toml::from_str::<Table>(contents).unwrap();
+        retain_style_keys(&mut table, "");
+        table
+    }
+
+    #[test]
+    fn bundled_themes_are_valid() {
+        for (name, config) in preset_configs("") {
+            assert!(
+                config.validate_styles().is_ok(),
+                "Theme '{name}' is invalid"
+            );
+            assert_eq!(
+                config.theme.console_palette.len(),
+                16,
+                "Theme '{name}' has no console palette"
+            );
+        }
+    }
+
+    #[test]
+    fn bundled_themes_style_with_the_palette() {
+        for (_, config) in preset_configs("") {
+            assert_eq!(config.username_field.style.title_color_focused, "accent");
+        }
+    }
+
+    #[test]
+    fn user_config_overrides_bundled_themes() {
+        let user_config = r#"
+            [theme.palette]
+            accent = "red"
+
+            [banner]
+            color = "blue"
+        "#;
+
+        for (_, config) in preset_configs(user_config) {
+            assert_eq!(config.theme.palette.0["accent"], "red");
+            assert_eq!(config.banner.color, "blue");
+        }
+    }
+
+    #[test]
+    fn theme_keeps_style_keys() {
+        let style = r#"
+            [theme]
+            console_palette = ["black"]
+
+            [theme.palette]
+            accent = "red"
+
+            [username_field.style]
+            title_color = "accent"
+            title_color_focused = "accent"
+        "#;
+
+        assert_eq!(style_keys(style), toml::from_str::<Table>(style).unwrap());
+    }
+
+    #[test]
+    fn theme_drops_other_settings() {
+        let table = style_keys(
+            r#"
+            pam_service = "other"
+
+            [theme]
+            name = "nord"
+
+            [username_field]
+            title = "Login"
+
+            [[power_controls.entries]]
+            hint = "Reboot"
+            cmd = "reboot"
+            "#,
+        );
+
+        let expected = toml::from_str::<Table>(
+            r#"
+            [theme]
+
+            [username_field]
+
+            [power_controls]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(table, expected);
+    }
+}
@@ -0,0 +1,192 @@
+use std::fs::read_to_string;
+
+use log::warn;
+use ratatui::{
+    layout::{Alignment, Rect},
+    style::Style,
+    text::{Line, Span, Text},
+    widgets::Paragraph,
+    Frame,
+};
+use unicode_width::UnicodeWidthStr;
+
+use crate::config::{get_color, get_modifiers, BannerConfig, TextAlignment};
+
+#[derive(Clone)]
+pub struct BannerWidget {
+    config: BannerConfig,
+    lines: Vec<String>,
+}
+
+/// Get the lines of the banner from the file or the configured text
+fn load_lines(config: &BannerConfig) -> Vec<String> {
+    if !config.show_banner {
+        return Vec::new();
+    }
+
+    let text = if config.path.is_empty() {
+        config.text.clone()
+    } else {
+        read_to_string(&config.path).unwrap_or_else(|err| {
+            warn!(
+                "Failed to read banner from '{}'. Using the configured text. Reason: {err}",
+                config.path
+            );
+            config.text.clone()
+        })
+    };
+
+    // Tabs have no width on the screen
+    text.lines()
+        .map(|line| line.replace('\t', "    "))
+        .collect()
+}
+
+impl BannerWidget {
+    pub fn new(config: BannerConfig) -> Self {
+        let lines = load_lines(&config);
+        Self { config, lines }
+    }
+
+    /// The amount of lines needed to show the banner
+    pub fn height(&self) -> u16 {
+        u16::try_from(self.lines.len()).unwrap_or(u16::MAX)
+    }
+
+    /// The amount of columns needed to show the banner
+    pub fn width(&self) -> u16 {
+        let width = self
+            .lines
+            .iter()
+            .map(|line| line.width())
+            .max()
+            .unwrap_or(0);
+        u16::try_from(width).unwrap_or(u16::MAX)
+    }
+
+    fn line_style(&self, index: usize) -> Style {
+        let color = self
+            .config
+            .line_colors
+            .get(index)
+            .unwrap_or(&self.config.color);
+
+        let mut style = Style::default().fg(get_color(color));
+
+        for modifier in get_modifiers(&self.config.modifiers) {
+            style = style.add_modifier(modifier);
+        }
+
+        style
+    }
+
+    pub fn render(&self, frame: &mut Frame<impl ratatui::backend::Backend>, area: Rect) {
+        if self.lines.is_empty() {
+            return;
+        }
+
+        let text = Text::from(
+            self.lines
+                .iter()
+                .enumerate()
+                .map(|(index, line)| {
+                    Line::from(Span::styled(line.as_str(), self.line_style(index)))
+                })
+                .collect::<Vec<Line>>(),
+        );
+
+        let alignment = match self.config.alignment {
+            TextAlignment::Left => Alignment::Left,
+            TextAlignment::Center => Alignment::Center,
+            TextAlignment::Right => Alignment::Right,
+        };
+
+        frame.render_widget(Paragraph::new(text).alignment(alignment), area);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ratatui::{backend::TestBackend, style::Color, Terminal};
+
+    use crate::config::Config;
+
+    fn banner(text: &str, alignment: TextAlignment, line_colors: &[&str]) -> BannerWidget {
+        let mut config = Config::default().banner;
+        config.show_banner = true;
+        config.path = String::new();
+        config.text = text.to_string();
+        config.alignment = alignment;
+        config.color = "blue".to_string();
+        config.line_colors = line_colors.iter().map(|color| color.to_string()).collect();
+
+        BannerWidget::new(config)
+    }
+
+    /// Render `banner` on a single line of 6 columns
+    fn rendered(banner: &BannerWidget) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(6, 1)).unwrap();
+        terminal
+            .draw(|frame| banner.render(frame, frame.size()))
+            .unwrap();
+
+        terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn line_colors_apply_to_lines_in_order() {
+        let banner = banner("a\nb", TextAlignment::Left, &["red", "green"]);
+
+        assert_eq!(banner.line_style(0).fg, Some(Color::Red));
+        assert_eq!(banner.line_style(1).fg, Some(Color::Green));
+    }
+
+    #[test]
+    fn lines_without_line_color_use_banner_color() {
+        let banner = banner("a\nb", TextAlignment::Left, &["red"]);
+
+        assert_eq!(banner.line_style(1).fg, Some(Color::Blue));
+    }
+
+    #[test]
+    fn banner_is_aligned_left() {
+        assert_eq!(rendered(&banner("ab", TextAlignment::Left, &[])), "ab    ");
+    }
+
+    #[test]
+    fn banner_is_aligned_center() {
+        assert_eq!(
+            rendered(&banner("ab", TextAlignment::Center, &[])),
+            "  ab  "
+        );
+    }
+
+    #[test]
+    fn banner_is_aligned_right() {
+        assert_eq!(rendered(&banner("ab", TextAlignment::Right, &[])), "    ab");
+    }
+
+    #[test]
+    fn tabs_are_expanded_to_spaces() {
+        let banner = banner("\tab\nc", TextAlignment::Left, &[]);
+
+        assert_eq!((banner.width(), banner.height()), (6, 2));
+    }
+
+    #[test]
+    fn hidden_banner_has_no_lines() {
+        let mut banner = banner("ab", TextAlignment::Left, &[]);
+        banner.config.show_banner = false;
+        banner.lines = load_lines(&banner.config);
+
+        assert_eq!(banner.height(), 0);
+    }
+}
@@ -1,47 +1,407 @@
-use ratatui::{
-    backend::Backend,
-    layout::{Constraint, Direction, Layout, Rect},
-    Frame,
-};
-use Constraint::{Length, Min};
+use log::warn;
+use ratatui::layout::{Margin, Rect};
+
+use crate::config::{Config, InputFieldStyle, LayoutAlignment, LayoutConfig};
+
+/// The widgets that are placed within the login form
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FormWidget {
+    Banner,
+    InfoPanel,
+    EnvironmentSwitcher,
+    LocaleSwitcher,
+    UsernameField,
+    PasswordField,
+    StatusMessage,
+}
+
+const DEFAULT_WIDGET_ORDER: [FormWidget; 7] = [
+    FormWidget::Banner,
+    FormWidget::InfoPanel,
+    FormWidget::EnvironmentSwitcher,
+    FormWidget::LocaleSwitcher,
+    FormWidget::UsernameField,
+    FormWidget::PasswordField,
+    FormWidget::StatusMessage,
+];
+
+/// The widgets that are still shown when the terminal is too small for the configured layout
+const ESSENTIAL_WIDGETS: [FormWidget; 4] = [
+    FormWidget::EnvironmentSwitcher,
+    FormWidget::UsernameField,
+    FormWidget::PasswordField,
+    FormWidget::StatusMessage,
+];
+
+/// The minimal width of the terminal, including the margins, to show the configured layout
+const MINIMAL_WIDTH: u16 = 24;
+
+impl FormWidget {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "banner" => Self::Banner,
+            "info_panel" => Self::InfoPanel,
+            "environment_switcher" => Self::EnvironmentSwitcher,
+            "locale_switcher" => Self::LocaleSwitcher,
+            "username_field" => Self::UsernameField,
+            "password_field" => Self::PasswordField,
+            "status_message" => Self::StatusMessage,
+            _ => return None,
+        })
+    }
+}
+
+/// The placement of the widgets as it follows from the configuration
+#[derive(Clone)]
+pub struct FormLayout {
+    config: LayoutConfig,
+    order: Vec<FormWidget>,
+    banner_height: u16,
+    info_panel_height: u16,
+    username_max_width: Option<u16>,
+    password_max_width: Option<u16>,
+}
 
 pub struct Chunks {
     pub key_menu: Rect,
+    pub form_border: Option<Rect>,
+    pub banner: Rect,
+    pub info_panel: Rect,
     pub switcher: Rect,
+    pub locale_switcher: Rect,
     pub username_field: Rect,
     pub password_field: Rect,
     pub status_message: Rect,
 }
 
 impl Chunks {
-    pub fn new<B: Backend>(frame: &Frame<B>) -> Self {
-        let constraints = [
-            Length(1),
-            Length(1),
-            Length(2),
-            Length(1),
-            Length(2),
-            Length(3),
-            Length(2),
-            Length(3),
-            Length(2),
-            Length(1),
-            Min(0),
-        ];
-
-        let chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .horizontal_margin(2)
-            .vertical_margin(1)
-            .constraints(constraints.as_ref())
-            .split(frame.size());
+    fn empty() -> Self {
+        Self {
+            key_menu: Rect::default(),
+            form_border: None,
+            banner: Rect::default(),
+            info_panel: Rect::default(),
+            switcher: Rect::default(),
+            locale_switcher: Rect::default(),
+            username_field: Rect::default(),
+            password_field: Rect::default(),
+            status_message: Rect::default(),
+        }
+    }
+}
+
+fn max_width(style: &InputFieldStyle) -> Option<u16> {
+    style.use_max_width.then_some(style.max_width)
+}
+
+impl FormLayout {
+    pub fn new(config: &Config, banner_height: u16, info_panel_height: u16) -> Self {
+        let mut order = Vec::new();
+
+        for name in &config.layout.widget_order {
+            match FormWidget::from_name(name) {
+                Some(widget) if !order.contains(&widget) => order.push(widget),
+                Some(_) => warn!("Widget '{name}' is listed multiple times in the layout"),
+                None => warn!("Unknown widget '{name}' in the layout. Skipping it."),
+            }
+        }
+
+        // Every widget is needed to log in, so missing widgets are still shown
+        for widget in DEFAULT_WIDGET_ORDER {
+            if !order.contains(&widget) {
+                order.push(widget);
+            }
+        }
+
+        if !config.locale_switcher.enabled {
+            order.retain(|widget| *widget != FormWidget::LocaleSwitcher);
+        }
+        if banner_height == 0 {
+            order.retain(|widget| *widget != FormWidget::Banner);
+        }
+        if info_panel_height == 0 {
+            order.retain(|widget| *widget != FormWidget::InfoPanel);
+        }
 
         Self {
-            key_menu: chunks[0],
-            switcher: chunks[3],
-            username_field: chunks[5],
-            password_field: chunks[7],
-            status_message: chunks[9],
+            config: config.layout.clone(),
+            order,
+            banner_height,
+            info_panel_height,
+            username_max_width: max_width(&config.username_field.style),
+            password_max_width: max_width(&config.password_field.style),
+        }
+    }
+
+    fn widget_height(&self, widget: FormWidget) -> u16 {
+        match widget {
+            FormWidget::Banner => self.banner_height,
+            FormWidget::InfoPanel => self.info_panel_height,
+            FormWidget::EnvironmentSwitcher
+            | FormWidget::LocaleSwitcher
+            | FormWidget::StatusMessage => 1,
+            FormWidget::UsernameField | FormWidget::PasswordField => 3,
+        }
+    }
+
+    /// Place the form box within `body`
+    fn form_area(&self, body: Rect, content_height: u16) -> Rect {
+        let config = &self.config;
+
+        let width = match config.alignment {
+            LayoutAlignment::TopLeft => body.width,
+            _ if config.width == 0 => body.width,
+            _ => config.width.min(body.width),
+        };
+        let x = match config.alignment {
+            LayoutAlignment::TopLeft | LayoutAlignment::Left => body.x,
+            LayoutAlignment::Center => body.x + (body.width - width) / 2,
+            LayoutAlignment::Right => body.right() - width,
+        };
+
+        let height = match config.alignment {
+            _ if config.height != 0 => config.height,
+            LayoutAlignment::Left | LayoutAlignment::Right => body.height,
+            _ => content_height,
+        }
+        .min(body.height);
+        let free_height = body.height - height;
+        let y = body.y
+            + if config.vertical_centering {
+                free_height / 2
+            } else {
+                config.spacing.min(free_height)
+            };
+
+        Rect::new(x, y, width, height)
+    }
+
+    /// Constrain the area of an input field to its maximum width
+    fn input_field_area(&self, area: Rect, max_width: Option<u16>) -> Rect {
+        match max_width {
+            Some(max_width) if max_width < area.width => Rect {
+                x: if self.config.horizontal_centering {
+                    area.x + (area.width - max_width) / 2
+                } else {
+                    area.x
+                },
+                width: max_width,
+                ..area
+            },
+            _ => area,
+        }
+    }
+
+    /// Whether the configured layout fits within `area` when all spacing is removed
+    fn fits(&self, area: Rect) -> bool {
+        let border_size = if self.config.show_border { 2 } else { 0 };
+        let widgets_height = self.order.iter().fold(0u16, |height, widget| {
+            height.saturating_add(self.widget_height(*widget))
+        });
+
+        // The margins, the key menu and the line below it
+        let required_height = widgets_height.saturating_add(border_size).saturating_add(4);
+
+        area.width >= MINIMAL_WIDTH && area.height >= required_height
+    }
+
+    /// Place the widgets below each other starting at the top of `area`
+    fn place_widgets(
+        &self,
+        chunks: &mut Chunks,
+        order: &[FormWidget],
+        area: Rect,
+        spacing: u16,
+        mut y: u16,
+    ) {
+        for widget in order {
+            let height = self.widget_height(*widget);
+            let widget_area = Rect::new(area.x, y, area.width, height);
+            let widget_area = if widget_area.intersects(area) {
+                widget_area.intersection(area)
+            } else {
+                Rect::default()
+            };
+            y = y.saturating_add(height).saturating_add(spacing);
+
+            match widget {
+                FormWidget::Banner => chunks.banner = widget_area,
+                FormWidget::InfoPanel => chunks.info_panel = widget_area,
+                FormWidget::EnvironmentSwitcher => chunks.switcher = widget_area,
+                FormWidget::LocaleSwitcher => chunks.locale_switcher = widget_area,
+                FormWidget::UsernameField => {
+                    chunks.username_field =
+                        self.input_field_area(widget_area, self.username_max_width)
+                }
+                FormWidget::PasswordField => {
+                    chunks.password_field =
+                        self.input_field_area(widget_area, self.password_max_width)
+                }
+                FormWidget::StatusMessage => chunks.status_message = widget_area,
+            }
+        }
+    }
+
+    /// Divide `area` between the essential widgets only, without any margins or spacing
+    fn minimal_chunks(&self, area: Rect) -> Chunks {
+        let mut chunks = Chunks::empty();
+        self.place_widgets(&mut chunks, &ESSENTIAL_WIDGETS, area, 0, area.y);
+        chunks
+    }
+
+    /// Divide `area` between the widgets
+    ///
+    /// On terminals that are too small, the spacing is reduced first. When the widgets still do
+    /// not fit, only the essential widgets are shown.
+    pub fn chunks(&self, area: Rect) -> Chunks {
+        let config = &self.config;
+
+        if !self.fits(area) {
+            return self.minimal_chunks(area);
+        }
+
+        let screen = area.inner(&Margin {
+            vertical: 1,
+            horizontal: 2,
+        });
+
+        let key_menu = Rect {
+            height: screen.height.min(1),
+            ..screen
+        };
+
+        // Keep an empty line below the key menu
+        let body_offset = screen.height.min(2);
+        let body = Rect {
+            y: screen.y + body_offset,
+            height: screen.height - body_offset,
+            ..screen
+        };
+
+        let border_size = if config.show_border { 2 } else { 0 };
+        let widgets_height = self.order.iter().fold(0u16, |height, widget| {
+            height.saturating_add(self.widget_height(*widget))
+        });
+        let gaps = self.order.len().saturating_sub(1) as u16;
+        let content_height = widgets_height
+            .saturating_add(gaps.saturating_mul(config.spacing))
+            .saturating_add(border_size);
+
+        let form = self.form_area(body, content_height);
+        let inner = if config.show_border {
+            form.inner(&Margin {
+                vertical: 1,
+                horizontal: 1,
+            })
+        } else {
+            form
+        };
+
+        let spacing =
+            if widgets_height.saturating_add(gaps.saturating_mul(config.spacing)) <= inner.height {
+                config.spacing
+            } else {
+                inner
+                    .height
+                    .saturating_sub(widgets_height)
+                    .checked_div(gaps)
+                    .unwrap_or(0)
+            };
+
+        let used_height = widgets_height + gaps * spacing;
+        let y = inner.y
+            + if config.vertical_centering {
+                inner.height.saturating_sub(used_height) / 2
+            } else {
+                0
+            };
+
+        let mut chunks = Chunks {
+            key_menu,
+            form_border: (config.show_border && form.width >= 2 && form.height >= 2)
+                .then_some(form),
+            ..Chunks::empty()
+        };
+        self.place_widgets(&mut chunks, &self.order, inner, spacing, y);
+
+        chunks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_layout() {
+        let layout = FormLayout::new(&Config::default(), 0, 0);
+        let chunks = layout.chunks(Rect::new(0, 0, 100, 40));
+
+        assert_eq!(chunks.key_menu, Rect::new(2, 1, 96, 1));
+        assert_eq!(chunks.switcher, Rect::new(2, 5, 96, 1));
+        assert_eq!(chunks.username_field, Rect::new(26, 8, 48, 3));
+        assert_eq!(chunks.password_field, Rect::new(26, 13, 48, 3));
+        assert_eq!(chunks.status_message, Rect::new(2, 18, 96, 1));
+        assert!(chunks.form_border.is_none());
+    }
+
+    #[test]
+    fn minimal_layout() {
+        let mut config = Config::default();
+        config.layout.show_border = true;
+        let layout = FormLayout::new(&config, 6, 0);
+
+        let chunks = layout.chunks(Rect::new(0, 0, 60, 12));
+        assert_eq!(chunks.key_menu, Rect::default());
+        assert_eq!(chunks.banner, Rect::default());
+        assert!(chunks.form_border.is_none());
+        assert_eq!(chunks.switcher, Rect::new(0, 0, 60, 1));
+        assert_eq!(chunks.username_field, Rect::new(6, 1, 48, 3));
+        assert_eq!(chunks.password_field, Rect::new(6, 4, 48, 3));
+        assert_eq!(chunks.status_message, Rect::new(0, 7, 60, 1));
+
+        // Everything is shown once the terminal is large enough
+        let chunks = layout.chunks(Rect::new(0, 0, 60, 20));
+        assert!(chunks.form_border.is_some());
+        assert_eq!(chunks.banner.height, 6);
+    }
+
+    #[test]
+    fn tiny_terminals() {
+        let mut config = Config::default();
+        config.locale_switcher.enabled = true;
+        config.layout.show_border = true;
+
+        for alignment in [
+            LayoutAlignment::TopLeft,
+            LayoutAlignment::Center,
+            LayoutAlignment::Left,
+            LayoutAlignment::Right,
+        ] {
+            config.layout.alignment = alignment;
+
+            for vertical_centering in [false, true] {
+                config.layout.vertical_centering = vertical_centering;
+                let layout = FormLayout::new(&config, 6, 2);
+
+                for (width, height) in [(0, 0), (1, 1), (4, 3), (10, 8), (30, 14)] {
+                    let area = Rect::new(0, 0, width, height);
+                    let chunks = layout.chunks(area);
+
+                    for chunk in [
+                        chunks.key_menu,
+                        chunks.banner,
+                        chunks.info_panel,
+                        chunks.switcher,
+                        chunks.locale_switcher,
+                        chunks.username_field,
+                        chunks.password_field,
+                        chunks.status_message,
+                    ] {
+                        assert_eq!(area.union(chunk), area, "{alignment:?} {width}x{height}");
+                    }
+                }
+            }
         }
     }
 }
@@ -0,0 +1,429 @@
+//! This module implements a panel with information about the machine.
+//!
+//! The panel shows a configurable format with tokens such as `%hostname%` and `%time%` and can
+//! additionally show `/etc/issue` with the escape sequences that `agetty` supports.
+
+use std::ffi::CString;
+use std::fs::read_to_string;
+use std::mem;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+
+use log::warn;
+use ratatui::{
+    layout::{Alignment, Rect},
+    style::Style,
+    text::{Line, Text},
+    widgets::Paragraph,
+    Frame,
+};
+
+use crate::auth::utmpx::logged_in_users;
+use crate::config::{get_color, get_modifiers, InfoPanelConfig, TextAlignment};
+
+const OS_RELEASE_PATHS: [&str; 2] = ["/etc/os-release", "/usr/lib/os-release"];
+
+/// The fields of `uname`
+struct SystemName {
+    sysname: String,
+    nodename: String,
+    release: String,
+    version: String,
+    machine: String,
+    domainname: String,
+}
+
+fn c_chars_to_string(chars: &[libc::c_char]) -> String {
+    let bytes: Vec<u8> = chars
+        .iter()
+        .take_while(|c| **c != 0)
+        .map(|c| *c as u8)
+        .collect();
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn system_name() -> SystemName {
+    let mut name: libc::utsname = unsafe { mem::zeroed() };
+
+    if unsafe { libc::uname(&mut name) } != 0 {
+        warn!("Failed to get the system name");
+    }
+
+    SystemName {
+        sysname: c_chars_to_string(&name.sysname),
+        nodename: c_chars_to_string(&name.nodename),
+        release: c_chars_to_string(&name.release),
+        version: c_chars_to_string(&name.version),
+        machine: c_chars_to_string(&name.machine),
+        domainname: c_chars_to_string(&name.domainname),
+    }
+}
+
+/// Format the current local time with a `strftime` format
+pub fn format_time(format: &str) -> String {
+    let Ok(format) = CString::new(format) else {
+        return String::new();
+    };
+
+    let mut buffer = [0 as libc::c_char; 256];
+
+    let length = unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut local_time: libc::tm = mem::zeroed();
+        if libc::localtime_r(&now, &mut local_time).is_null() {
+            return String::new();
+        }
+
+        libc::strftime(
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            format.as_ptr(),
+            &local_time,
+        )
+    };
+
+    c_chars_to_string(&buffer[..length])
+}
+
+/// Get the addresses of all local interfaces that are up, except for the loopback interfaces
+fn local_addresses() -> Vec<IpAddr> {
+    let mut addresses = Vec::new();
+    let mut interfaces: *mut libc::ifaddrs = std::ptr::null_mut();
+
+    if unsafe { libc::getifaddrs(&mut interfaces) } != 0 {
+        warn!("Failed to get the network interfaces");
+        return addresses;
+    }
+
+    let mut interface = interfaces;
+    while !interface.is_null() {
+        let ifaddrs = unsafe { &*interface };
+        interface = ifaddrs.ifa_next;
+
+        let flags = ifaddrs.ifa_flags as libc::c_int;
+        if ifaddrs.ifa_addr.is_null()
+            || flags & libc::IFF_UP == 0
+            || flags & libc::IFF_LOOPBACK != 0
+        {
+            continue;
+        }
+
+        match unsafe { (*ifaddrs.ifa_addr).sa_family } as libc::c_int {
+            libc::AF_INET => {
+                let address = unsafe { &*(ifaddrs.ifa_addr as *const libc::sockaddr_in) };
+                addresses.push(IpAddr::V4(Ipv4Addr::from(u32::from_be(
+                    address.sin_addr.s_addr,
+                ))));
+            }
+            libc::AF_INET6 => {
+                let address = unsafe { &*(ifaddrs.ifa_addr as *const libc::sockaddr_in6) };
+                addresses.push(IpAddr::V6(Ipv6Addr::from(address.sin6_addr.s6_addr)));
+            }
+            _ => {}
+        }
+    }
+
+    unsafe { libc::freeifaddrs(interfaces) };
+
+    addresses
+}
+
+fn first_ipv4(addresses: &[IpAddr]) -> String {
+    addresses
+        .iter()
+        .find(|address| address.is_ipv4())
+        .map(IpAddr::to_string)
+        .unwrap_or_default()
+}
+
+fn first_ipv6(addresses: &[IpAddr]) -> String {
+    addresses
+        .iter()
+        .find(|address| address.is_ipv6())
+        .map(IpAddr::to_string)
+        .unwrap_or_default()
+}
+
+/// Get a variable from the `os-release` file. Quotes around the value are removed.
+fn os_release_value(key: &str) -> String {
+    let Some(content) = OS_RELEASE_PATHS
+        .iter()
+        .find_map(|path| read_to_string(path).ok())
+    else {
+        return String::new();
+    };
+
+    content
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .find(|(line_key, _)| line_key.trim() == key)
+        .map(|(_, value)| value.trim().trim_matches(['"', '\''].as_ref()).to_string())
+        .unwrap_or_default()
+}
+
+fn users_text(users: usize) -> String {
+    if users == 1 {
+        "1 user".to_string()
+    } else {
+        format!("{users} users")
+    }
+}
+
+/// Replace the `agetty` escape sequences within the contents of `/etc/issue`
+///
+/// Terminal control sequences (`\e`) cannot be shown within the TUI and are removed.
+fn expand_issue(issue: &str, tty: u8) -> String {
+    let system = system_name();
+    let mut cached_addresses = None;
+    let mut addresses = || cached_addresses.get_or_insert_with(local_addresses).clone();
+
+    let mut expanded = String::with_capacity(issue.len());
+    let mut chars = issue.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            expanded.push(c);
+            continue;
+        }
+
+        let Some(escape) = chars.next() else {
+            expanded.push(c);
+            break;
+        };
+
+        // Some escapes take an argument between braces
+        let argument = if chars.peek() == Some(&'{') {
+            chars.next();
+            Some(chars.by_ref().take_while(|c| *c != '}').collect::<String>())
+        } else {
+            None
+        };
+
+        match escape {
+            's' => expanded.push_str(&system.sysname),
+            'n' => expanded.push_str(&system.nodename),
+            'r' => expanded.push_str(&system.release),
+            'v' => expanded.push_str(&system.version),
+            'm' => expanded.push_str(&system.machine),
+            'o' => expanded.push_str(&system.domainname),
+            'd' => expanded.push_str(&format_time("%a %b %e %Y")),
+            't' => expanded.push_str(&format_time("%H:%M:%S")),
+            'l' => expanded.push_str(&format!("tty{tty}")),
+            'u' => expanded.push_str(&logged_in_users().to_string()),
+            'U' => expanded.push_str(&users_text(logged_in_users())),
+            '4' => expanded.push_str(&first_ipv4(&addresses())),
+            '6' => expanded.push_str(&first_ipv6(&addresses())),
+            'S' => expanded.push_str(&os_release_value(
+                argument.as_deref().unwrap_or("PRETTY_NAME"),
+            )),
+            // Skip the control sequence that follows the escape character
+            'e' if argument.is_none() && chars.peek() == Some(&'[') => {
+                chars.next();
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            'e' => {}
+            '\\' => expanded.push('\\'),
+            _ => {
+                expanded.push(c);
+                expanded.push(escape);
+                if let Some(argument) = argument {
+                    expanded.push_str(&format!("{{{argument}}}"));
+                }
+            }
+        }
+    }
+
+    expanded
+}
+
+/// Replace the tokens within the format of the info panel
+fn expand_format(format: &str, time_format: &str, tty: u8) -> String {
+    let mut expanded = format.to_string();
+
+    // Only gather the information that is actually shown
+    let tokens: [(&str, &dyn Fn() -> String); 6] = [
+        ("%hostname%", &|| system_name().nodename),
+        ("%time%", &|| format_time(time_format)),
+        ("%kernel%", &|| system_name().release),
+        ("%ip%", &|| {
+            let addresses = local_addresses();
+            let ipv4 = first_ipv4(&addresses);
+            if ipv4.is_empty() {
+                first_ipv6(&addresses)
+            } else {
+                ipv4
+            }
+        }),
+        ("%tty%", &|| format!("tty{tty}")),
+        ("%users%", &|| logged_in_users().to_string()),
+    ];
+
+    for (token, value) in tokens {
+        if expanded.contains(token) {
+            expanded = expanded.replace(token, &value());
+        }
+    }
+
+    expanded
+}
+
+#[derive(Clone)]
+pub struct InfoPanelWidget {
+    config: InfoPanelConfig,
+    tty: u8,
+    issue: String,
+    /// The expanded format and issue, which is only updated with [`InfoPanelWidget::refresh`]
+    text: String,
+}
+
+impl InfoPanelWidget {
+    pub fn new(config: InfoPanelConfig, tty: u8) -> Self {
+        let issue = if config.show_info_panel && config.show_issue {
+            read_to_string(&config.issue_path).unwrap_or_else(|err| {
+                warn!(
+                    "Failed to read issue file '{}'. Reason: {err}",
+                    config.issue_path
+                );
+                String::new()
+            })
+        } else {
+            String::new()
+        };
+
+        let mut info_panel = Self {
+            config,
+            tty,
+            issue,
+            text: String::new(),
+        };
+        info_panel.refresh();
+        info_panel
+    }
+
+    /// Gather the information shown on the panel again
+    pub fn refresh(&mut self) {
+        if !self.config.show_info_panel {
+            return;
+        }
+
+        let format = expand_format(&self.config.format, &self.config.time_format, self.tty);
+        let issue = expand_issue(self.issue.trim_end(), self.tty);
+
+        self.text = if issue.is_empty() {
+            format
+        } else {
+            format!("{format}\n{issue}")
+        };
+    }
+
+    /// The amount of lines needed to show the panel
+    pub fn height(&self) -> u16 {
+        if !self.config.show_info_panel {
+            return 0;
+        }
+
+        let lines = self.config.format.lines().count() + self.issue.trim_end().lines().count();
+        u16::try_from(lines).unwrap_or(u16::MAX)
+    }
+
+    /// How often the panel needs to be redrawn to stay up-to-date
+    pub fn refresh_interval(&self) -> Option<Duration> {
+        (self.config.show_info_panel && self.config.refresh_interval_secs != 0)
+            .then(|| Duration::from_secs(self.config.refresh_interval_secs.into()))
+    }
+
+    fn style(&self) -> Style {
+        let mut style = Style::default().fg(get_color(&self.config.color));
+
+        for modifier in get_modifiers(&self.config.modifiers) {
+            style = style.add_modifier(modifier);
+        }
+
+        style
+    }
+
+    pub fn render(&self, frame: &mut Frame<impl ratatui::backend::Backend>, area: Rect) {
+        if !self.config.show_info_panel {
+            return;
+        }
+
+        let text = Text::from(
+            self.text
+                .lines()
+                .map(|line| Line::from(line.to_string()))
+                .collect::<Vec<Line>>(),
+        );
+
+        let alignment = match self.config.alignment {
+            TextAlignment::Left => Alignment::Left,
+            TextAlignment::Center => Alignment::Center,
+            TextAlignment::Right => Alignment::Right,
+        };
+
+        frame.render_widget(
+            Paragraph::new(text)
+                .style(self.style())
+                .alignment(alignment),
+            area,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn issue_tty_escape_is_expanded() {
+        assert_eq!(expand_issue("Welcome on \\l", 2), "Welcome on tty2");
+    }
+
+    #[test]
+    fn issue_backslash_is_unescaped() {
+        assert_eq!(expand_issue("a\\\\b", 1), "a\\b");
+    }
+
+    #[test]
+    fn issue_terminal_sequences_are_removed() {
+        assert_eq!(expand_issue("\\e[1;31mred\\e[0m \\e{reset}", 1), "red ");
+    }
+
+    #[test]
+    fn unknown_issue_escapes_are_kept() {
+        assert_eq!(expand_issue("\\x{y} \\", 1), "\\x{y} \\");
+    }
+
+    #[test]
+    fn issue_system_escapes_are_expanded() {
+        assert_eq!(
+            expand_issue("\\s \\r", 1),
+            format!("{} {}", system_name().sysname, system_name().release)
+        );
+    }
+
+    #[test]
+    fn format_tty_token_is_expanded() {
+        assert_eq!(expand_format("on %tty%", "", 3), "on tty3");
+    }
+
+    #[test]
+    fn unknown_format_tokens_are_kept() {
+        assert_eq!(expand_format("%unknown%", "", 3), "%unknown%");
+    }
+
+    #[test]
+    fn format_time_token_uses_the_time_format() {
+        assert_eq!(expand_format("%time%", "static", 3), "static");
+    }
+
+    #[test]
+    fn single_user_is_singular() {
+        assert_eq!(users_text(1), "1 user");
+        assert_eq!(users_text(2), "2 users");
+    }
+}
@@ -10,6 +10,8 @@ use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 use crate::config::{get_color, InputFieldStyle};
 
+use super::messages::text;
+
 /// The type of the input field display. How are the characters which are typed displayed?
 #[derive(Clone)]
 pub enum InputFieldDisplayType {
@@ -244,7 +246,7 @@ impl InputFieldWidget {
         }
     }
 
-    fn get_block(&self, is_focused: bool) -> Block {
+    fn get_block(&self, is_focused: bool) -> Block<'_> {
         let (title_style, border_style) = if is_focused {
             (
                 Style::default().fg(get_color(&self.style.title_color_focused)),
@@ -260,7 +262,7 @@ impl InputFieldWidget {
         let block = Block::default();
 
         let block = if self.style.show_title {
-            block.title(Span::styled(self.style.title.clone(), title_style))
+            block.title(Span::styled(text(&self.style.title), title_style))
         } else {
             block
         };
@@ -281,7 +283,7 @@ impl InputFieldWidget {
         // Check whether a maximum width has been set
         if style.use_max_width && style.max_width < area.width {
             // Center the area
-            area.x = (area.width - style.max_width) / 2;
+            area.x += (area.width - style.max_width) / 2;
             area.width = style.max_width;
         }
 
@@ -298,8 +300,9 @@ impl InputFieldWidget {
         let block = self.get_block(is_focused);
         let inner = block.inner(area);
 
-        // Get width of text field minus borders (2)
-        self.width = inner.width;
+        // Get width of text field minus borders (2). This should never be 0 to avoid issues on
+        // tiny terminals.
+        self.width = inner.width.max(1);
 
         let show_string = self.show_string();
 
@@ -12,6 +12,8 @@ use crate::config::{
     SwitcherVisibility,
 };
 
+use super::messages::text;
+
 #[derive(Clone)]
 pub struct KeyMenuWidget {
     power_config: PowerControlConfig,
@@ -70,7 +72,7 @@ impl KeyMenuWidget {
             ));
             items.push(Span::raw(" "));
             items.push(Span::styled(
-                power_control.hint.as_str(),
+                text(&power_control.hint),
                 power_control.style(),
             ));
 
@@ -86,9 +88,7 @@ impl KeyMenuWidget {
         if let SwitcherVisibility::Keybind(KeyCode::F(n)) = self.switcher_config.switcher_visibility
         {
             let right_widget = Paragraph::new(
-                self.switcher_config
-                    .toggle_hint
-                    .replace("%key%", &format!("F{n}")),
+                text(&self.switcher_config.toggle_hint).replace("%key%", &format!("F{n}")),
             )
             .alignment(Alignment::Right)
             .style(self.switcher_toggle_style());
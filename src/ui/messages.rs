@@ -0,0 +1,319 @@
+//! This module implements the message catalogs that are used to translate the UI.
+//!
+//! A catalog contains the built-in messages of Lemurs by key and translations of the texts that are
+//! set within the configuration. Anything missing from the catalog of the selected language falls
+//! back to English.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs::read_to_string;
+use std::path::Path;
+use std::sync::{OnceLock, RwLock};
+
+use log::{error, info, warn};
+use serde::Deserialize;
+
+use crate::config::MessagesConfig;
+
+/// The built-in messages of Lemurs
+#[derive(Debug, Clone, Copy)]
+pub enum Message {
+    AuthenticationFailed,
+    NoGraphicalEnvironment,
+    FailedGraphicalEnvironment,
+    FailedDesktop,
+    FailedPowerControl,
+    LoggingIn,
+    Authenticating,
+}
+
+impl Message {
+    fn key(self) -> &'static str {
+        use Message::*;
+
+        match self {
+            AuthenticationFailed => "authentication_failed",
+            NoGraphicalEnvironment => "no_graphical_environment",
+            FailedGraphicalEnvironment => "failed_graphical_environment",
+            FailedDesktop => "failed_desktop",
+            FailedPowerControl => "failed_power_control",
+            LoggingIn => "logging_in",
+            Authenticating => "authenticating",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct Catalog {
+    #[serde(default)]
+    messages: HashMap<String, String>,
+    #[serde(default)]
+    texts: HashMap<String, String>,
+}
+
+/// The catalog that is currently in use together with the locale it was selected for
+struct ActiveCatalog {
+    locale: String,
+    catalog: Catalog,
+}
+
+static ENGLISH: OnceLock<Catalog> = OnceLock::new();
+static ACTIVE: RwLock<Option<ActiveCatalog>> = RwLock::new(None);
+
+fn english() -> &'static Catalog {
+    ENGLISH.get_or_init(|| {
+        toml::from_str(include_str!("../../extra/locale/en.toml")).unwrap_or_else(|err| {
+            error!("English message catalog cannot be properly parsed: {err}");
+            Catalog::default()
+        })
+    })
+}
+
+/// Get the catalog names to try for `locale` from most to least specific
+///
+/// For example, `de_AT.UTF-8@euro` gives `de_AT@euro`, `de_AT`, `de@euro` and `de`.
+fn language_candidates(locale: &str) -> Vec<String> {
+    let (base, modifier) = match locale.split_once('@') {
+        Some((base, modifier)) => (base, Some(modifier)),
+        None => (locale, None),
+    };
+
+    // The codeset does not matter for the catalog
+    let territory_language = base.split('.').next().unwrap_or_default();
+    let language = territory_language.split('_').next().unwrap_or_default();
+
+    if matches!(language, "" | "C" | "POSIX") {
+        return Vec::new();
+    }
+
+    let names = if territory_language == language {
+        vec![language]
+    } else {
+        vec![territory_language, language]
+    };
+
+    let mut candidates = Vec::new();
+    for name in names {
+        if let Some(modifier) = modifier {
+            candidates.push(format!("{name}@{modifier}"));
+        }
+        candidates.push(name.to_string());
+    }
+
+    candidates
+}
+
+/// Get the locale that the messages of Lemurs should follow
+fn messages_locale(config: &MessagesConfig, selected_locale: Option<&str>) -> String {
+    if !config.language.is_empty() {
+        return config.language.clone();
+    }
+
+    if let Some(selected_locale) = selected_locale {
+        return selected_locale.to_string();
+    }
+
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .filter_map(|key| env::var(key).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default()
+}
+
+fn load_catalog(catalogs_path: &Path, locale: &str) -> Catalog {
+    for candidate in language_candidates(locale) {
+        // The locale may come from the environment, so the catalog should not be able to escape
+        // the catalogs directory
+        if candidate.contains('/') {
+            continue;
+        }
+
+        let path = catalogs_path.join(format!("{candidate}.toml"));
+        if !path.is_file() {
+            continue;
+        }
+
+        match read_to_string(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|content| toml::from_str(&content).map_err(|err| err.to_string()))
+        {
+            Ok(catalog) => {
+                info!("Loaded message catalog '{}'", path.display());
+                return catalog;
+            }
+            Err(err) => warn!(
+                "Failed to load message catalog '{}'. Reason: {err}",
+                path.display()
+            ),
+        }
+    }
+
+    info!("No message catalog found for locale '{locale}'. Using English.");
+    Catalog::default()
+}
+
+/// Select the catalog of the language that the UI should be shown in
+///
+/// The language from the configuration takes precedence over the `selected_locale` of the locale
+/// switcher, which takes precedence over the locale Lemurs was started with.
+pub fn select_language(config: &MessagesConfig, selected_locale: Option<&str>) {
+    let locale = messages_locale(config, selected_locale);
+
+    let mut active = ACTIVE
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    if active
+        .as_ref()
+        .is_some_and(|active| active.locale == locale)
+    {
+        return;
+    }
+
+    let catalog = load_catalog(Path::new(&config.catalogs_path), &locale);
+    *active = Some(ActiveCatalog { locale, catalog });
+}
+
+/// Get the translation of a built-in message
+pub fn message(message: Message) -> String {
+    message_with(message, &[])
+}
+
+/// Get the translation of a built-in message and replace `%name%` with the value of the argument
+/// `name`
+pub fn message_with(message: Message, arguments: &[(&str, &str)]) -> String {
+    let key = message.key();
+
+    let active = ACTIVE
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    let mut text = active
+        .as_ref()
+        .and_then(|active| active.catalog.messages.get(key))
+        .or_else(|| english().messages.get(key))
+        .cloned()
+        .unwrap_or_else(|| key.to_string());
+
+    for (name, value) in arguments {
+        text = text.replace(&format!("%{name}%"), value);
+    }
+
+    text
+}
+
+/// Get the translation of a text that was set within the configuration
+pub fn text(configured: &str) -> String {
+    let active = ACTIVE
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    active
+        .as_ref()
+        .and_then(|active| active.catalog.texts.get(configured))
+        .cloned()
+        .unwrap_or_else(|| configured.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn english_catalog_has_every_message() {
+        use Message::*;
+
+        for message in [
+            AuthenticationFailed,
+            NoGraphicalEnvironment,
+            FailedGraphicalEnvironment,
+            FailedDesktop,
+            FailedPowerControl,
+            LoggingIn,
+            Authenticating,
+        ] {
+            assert!(
+                english().messages.contains_key(message.key()),
+                "English message '{}' is missing",
+                message.key()
+            );
+        }
+    }
+
+    fn catalogs_dir(name: &str, catalogs: &[(&str, &str)]) -> std::path::PathBuf {
+        let dir = env::temp_dir().join(format!(
+            "lemurs-messages-test-{name}-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        for (language, logging_in) in catalogs {
+            let path = dir.join(format!("{language}.toml"));
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, format!("[messages]\nlogging_in = \"{logging_in}\"\n")).unwrap();
+        }
+
+        dir
+    }
+
+    #[test]
+    fn territory_falls_back_to_language() {
+        assert_eq!(language_candidates("nl_NL"), vec!["nl_NL", "nl"]);
+        assert_eq!(language_candidates("fr"), vec!["fr"]);
+    }
+
+    #[test]
+    fn codeset_does_not_select_catalog() {
+        assert_eq!(language_candidates("nl_NL.UTF-8"), vec!["nl_NL", "nl"]);
+    }
+
+    #[test]
+    fn modifier_is_tried_before_plain_names() {
+        assert_eq!(
+            language_candidates("de_AT.UTF-8@euro"),
+            vec!["de_AT@euro", "de_AT", "de@euro", "de"]
+        );
+        assert_eq!(language_candidates("sr@latin"), vec!["sr@latin", "sr"]);
+    }
+
+    #[test]
+    fn c_locale_has_no_catalog() {
+        assert!(language_candidates("C.UTF-8").is_empty());
+        assert!(language_candidates("POSIX").is_empty());
+        assert!(language_candidates("").is_empty());
+    }
+
+    #[test]
+    fn configured_language_takes_precedence() {
+        let mut config = crate::config::Config::default().messages;
+        config.language = "de".to_string();
+
+        assert_eq!(messages_locale(&config, Some("nl_NL.UTF-8")), "de");
+
+        config.language = String::new();
+        assert_eq!(messages_locale(&config, Some("nl_NL.UTF-8")), "nl_NL.UTF-8");
+    }
+
+    #[test]
+    fn most_specific_catalog_is_loaded() {
+        let dir = catalogs_dir("specific", &[("nl", "Inloggen"), ("nl_BE", "Aanmelden")]);
+
+        let catalog = load_catalog(&dir, "nl_BE.UTF-8");
+        assert_eq!(catalog.messages["logging_in"], "Aanmelden");
+
+        let catalog = load_catalog(&dir, "nl_NL.UTF-8");
+        assert_eq!(catalog.messages["logging_in"], "Inloggen");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn catalog_cannot_come_from_another_directory() {
+        let dir = catalogs_dir("escape", &[("sub/nl", "Inloggen")]);
+
+        assert!(load_catalog(&dir, "sub/nl").messages.is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -3,37 +3,53 @@ use log::{error, info, warn};
 use std::io;
 use std::sync::mpsc::channel;
 use std::sync::{Arc, Mutex, MutexGuard};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use crate::config::{Config, FocusBehaviour, SwitcherVisibility};
+use crate::color::{self, ConsolePalette};
+use crate::config::{
+    get_color, resolve_color, Config, FocusBehaviour, SwitcherConfig, SwitcherVisibility,
+};
+use crate::console_font::ConsoleFont;
 use crate::info_caching::{get_cached_information, set_cache};
-use crate::post_login::PostLoginEnvironment;
+use crate::locale::get_locales;
+use crate::post_login::{EnvironmentStartError, PostLoginEnvironment};
 use crate::{start_session, Hooks, StartSessionError};
 use status_message::StatusMessage;
 
 use crossterm::cursor::MoveTo;
-use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use crossterm::event::{Event, KeyCode, KeyModifiers};
 use crossterm::execute;
 use crossterm::terminal::{
     disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen,
 };
 use ratatui::backend::CrosstermBackend;
+use ratatui::style::Style;
+use ratatui::widgets::{Block, Borders};
 use ratatui::{backend::Backend, Frame, Terminal};
 
 mod background;
+mod banner;
 mod chunks;
+mod info_panel;
 mod input_field;
 mod key_menu;
+mod messages;
+mod reactor;
+mod screensaver;
 mod status_message;
 mod switcher;
 
-use chunks::Chunks;
+use chunks::{Chunks, FormLayout};
 use input_field::{InputFieldDisplayType, InputFieldWidget};
 use key_menu::KeyMenuWidget;
+use reactor::{Reactor, ReactorEvent, TaskEvent, TaskSender, Timer};
 use status_message::{ErrorStatusMessage, InfoStatusMessage};
 use switcher::{SwitcherItem, SwitcherWidget};
 
 use self::background::BackgroundWidget;
+use self::banner::BannerWidget;
+use self::info_panel::InfoPanelWidget;
+use self::screensaver::{IdleState, ScreensaverWidget};
 
 #[derive(Clone)]
 struct LoginFormInputMode(Arc<Mutex<InputMode>>);
@@ -43,7 +59,7 @@ impl LoginFormInputMode {
         Self(Arc::new(Mutex::new(mode)))
     }
 
-    fn get_guard(&self) -> MutexGuard<InputMode> {
+    fn get_guard(&self) -> MutexGuard<'_, InputMode> {
         let Self(mutex) = self;
 
         match mutex.lock() {
@@ -59,26 +75,27 @@ impl LoginFormInputMode {
         *self.get_guard()
     }
 
-    fn prev(&self, skip_switcher: bool) {
-        self.get_guard().prev(skip_switcher)
+    fn prev(&self, skip_switcher: bool, skip_locale: bool) {
+        self.get_guard().prev(skip_switcher, skip_locale)
     }
-    fn next(&self, skip_switcher: bool) {
-        self.get_guard().next(skip_switcher)
+    fn next(&self, skip_switcher: bool, skip_locale: bool) {
+        self.get_guard().next(skip_switcher, skip_locale)
     }
     fn set(&self, mode: InputMode) {
         *self.get_guard() = mode;
     }
 }
 
+/// The status message together with the moment it was set
 #[derive(Clone)]
-struct LoginFormStatusMessage(Arc<Mutex<Option<StatusMessage>>>);
+struct LoginFormStatusMessage(Arc<Mutex<Option<(StatusMessage, Instant)>>>);
 
 impl LoginFormStatusMessage {
     fn new() -> Self {
         Self(Arc::new(Mutex::new(None)))
     }
 
-    fn get_guard(&self) -> MutexGuard<Option<StatusMessage>> {
+    fn get_guard(&self) -> MutexGuard<'_, Option<(StatusMessage, Instant)>> {
         let Self(mutex) = self;
 
         match mutex.lock() {
@@ -91,14 +108,31 @@ impl LoginFormStatusMessage {
     }
 
     fn get(&self) -> Option<StatusMessage> {
-        self.get_guard().clone()
+        self.get_guard().as_ref().map(|(msg, _)| msg.clone())
     }
 
     fn clear(&self) {
         *self.get_guard() = None;
     }
     fn set(&self, msg: impl Into<StatusMessage>) {
-        *self.get_guard() = Some(msg.into());
+        *self.get_guard() = Some((msg.into(), Instant::now()));
+    }
+
+    /// The time left until the shown error message should be cleared
+    fn error_expiry(&self, timeout: Duration) -> Option<Duration> {
+        self.get_guard()
+            .as_ref()
+            .filter(|(msg, _)| msg.is_error())
+            .map(|(_, set_at)| timeout.saturating_sub(set_at.elapsed()))
+    }
+    fn clear_expired_error(&self, timeout: Duration) {
+        let mut guard = self.get_guard();
+        if guard
+            .as_ref()
+            .is_some_and(|(msg, set_at)| msg.is_error() && set_at.elapsed() >= timeout)
+        {
+            *guard = None;
+        }
     }
 }
 
@@ -108,6 +142,9 @@ enum InputMode {
     /// Using the env switcher widget
     Switcher,
 
+    /// Using the locale switcher widget
+    Locale,
+
     /// Typing within the Username input field
     Username,
 
@@ -120,60 +157,75 @@ enum InputMode {
 
 impl InputMode {
     /// Move to the next mode
-    fn next(&mut self, skip_switcher: bool) {
+    fn next(&mut self, skip_switcher: bool, skip_locale: bool) {
         use InputMode::*;
 
         *self = match self {
             Normal => {
-                if skip_switcher {
+                if !skip_switcher {
+                    Switcher
+                } else if !skip_locale {
+                    Locale
+                } else {
+                    Username
+                }
+            }
+            Switcher => {
+                if skip_locale {
                     Username
                 } else {
-                    Switcher
+                    Locale
                 }
             }
-            Switcher => Username,
+            Locale => Username,
             Username => Password,
             Password => Password,
         }
     }
 
     /// Move to the previous mode
-    fn prev(&mut self, skip_switcher: bool) {
+    fn prev(&mut self, skip_switcher: bool, skip_locale: bool) {
         use InputMode::*;
 
         *self = match self {
             Normal => Normal,
             Switcher => Normal,
-            Username => {
+            Locale => {
                 if skip_switcher {
                     Normal
                 } else {
                     Switcher
                 }
             }
+            Username => {
+                if !skip_locale {
+                    Locale
+                } else if !skip_switcher {
+                    Switcher
+                } else {
+                    Normal
+                }
+            }
             Password => Username,
         }
     }
 }
 
-enum UIThreadRequest {
-    Redraw,
-    DisableTui,
-    EnableTui,
-    StopDrawing,
-}
-
 #[derive(Clone)]
 struct Widgets {
     background: BackgroundWidget,
+    banner: BannerWidget,
+    info_panel: InfoPanelWidget,
+    screensaver: ScreensaverWidget,
     key_menu: KeyMenuWidget,
     environment: Arc<Mutex<SwitcherWidget<PostLoginEnvironment>>>,
+    locale: Arc<Mutex<SwitcherWidget<String>>>,
     username: Arc<Mutex<InputFieldWidget>>,
     password: Arc<Mutex<InputFieldWidget>>,
 }
 
 impl Widgets {
-    fn environment_guard(&self) -> MutexGuard<SwitcherWidget<PostLoginEnvironment>> {
+    fn environment_guard(&self) -> MutexGuard<'_, SwitcherWidget<PostLoginEnvironment>> {
         match self.environment.lock() {
             Ok(guard) => guard,
             Err(err) => {
@@ -182,7 +234,16 @@ impl Widgets {
             }
         }
     }
-    fn username_guard(&self) -> MutexGuard<InputFieldWidget> {
+    fn locale_guard(&self) -> MutexGuard<'_, SwitcherWidget<String>> {
+        match self.locale.lock() {
+            Ok(guard) => guard,
+            Err(err) => {
+                error!("Lock failed. Reason: {}", err);
+                std::process::exit(1);
+            }
+        }
+    }
+    fn username_guard(&self) -> MutexGuard<'_, InputFieldWidget> {
         match self.username.lock() {
             Ok(guard) => guard,
             Err(err) => {
@@ -191,7 +252,7 @@ impl Widgets {
             }
         }
     }
-    fn password_guard(&self) -> MutexGuard<InputFieldWidget> {
+    fn password_guard(&self) -> MutexGuard<'_, InputFieldWidget> {
         match self.password.lock() {
             Ok(guard) => guard,
             Err(err) => {
@@ -209,6 +270,19 @@ impl Widgets {
     fn environment_try_select(&self, title: &str) {
         self.environment_guard().try_select(title);
     }
+    fn set_environments(&self, envs: Vec<(String, PostLoginEnvironment)>) {
+        self.environment_guard().set_items(
+            envs.into_iter()
+                .map(|(title, content)| SwitcherItem::new(title, content))
+                .collect(),
+        );
+    }
+    fn get_locale(&self) -> Option<String> {
+        self.locale_guard().selected().map(|s| s.content.clone())
+    }
+    fn locale_try_select(&self, locale: &str) {
+        self.locale_guard().try_select(locale);
+    }
     fn get_username(&self) -> String {
         self.username_guard().get_content()
     }
@@ -239,8 +313,10 @@ impl LoginForm {
     fn set_cache(&self) {
         let env_remember = self.config.environment_switcher.remember;
         let username_remember = self.config.username_field.remember;
+        let locale_remember =
+            self.config.locale_switcher.enabled && self.config.locale_switcher.remember;
 
-        if !env_remember && !username_remember {
+        if !env_remember && !username_remember && !locale_remember {
             info!("Nothing to cache.");
             return;
         }
@@ -256,28 +332,109 @@ impl LoginForm {
             .remember
             .then_some(self.widgets.get_username());
 
+        let locale_username = self.widgets.get_username();
+        let locale = if locale_remember && !locale_username.is_empty() {
+            self.widgets.get_locale()
+        } else {
+            None
+        };
+
         info!("Setting cached information");
-        set_cache(selected_env.as_deref(), username.as_deref(), &self.config);
+        set_cache(
+            selected_env.as_deref(),
+            username.as_deref(),
+            locale
+                .as_deref()
+                .map(|locale| (locale_username.as_str(), locale)),
+            &self.config,
+        );
     }
 
-    fn load_cache(&self) {
+    fn load_cache(&self, task_sender: &TaskSender) {
         let env_remember = self.config.environment_switcher.remember;
         let username_remember = self.config.username_field.remember;
 
         let cached = get_cached_information(&self.config);
+        let cached_env = cached.environment().filter(|_| env_remember);
 
         if username_remember {
             if let Some(username) = cached.username() {
                 info!("Loading username '{}' from cache", username);
                 self.widgets.set_username(username);
+                // The cached environment might be one of the user's environments
+                self.refresh_user_environments(task_sender, cached_env.map(str::to_string));
+                self.refresh_user_locale();
             }
         }
-        if env_remember {
-            if let Some(env) = cached.environment() {
-                info!("Loading environment '{}' from cache", env);
-                self.widgets.environment_try_select(env);
+        if let Some(env) = cached_env {
+            info!("Loading environment '{}' from cache", env);
+            self.widgets.environment_try_select(env);
+        }
+    }
+
+    /// Reload the environments to include the ones provided by the currently entered user
+    ///
+    /// The home directory of the user is searched by a background task, so a slow home directory
+    /// does not block the UI. The environments are shown once it sends them back. The environment
+    /// `select` is then selected, if it is given.
+    fn refresh_user_environments(&self, task_sender: &TaskSender, select: Option<String>) {
+        if !self.config.user_sessions.enabled {
+            return;
+        }
+
+        // The environments of the previous user may not be used by the new user in the meantime
+        let mut envs = crate::post_login::get_envs(&self.config);
+        self.widgets.set_environments(envs.clone());
+
+        let username = self.widgets.get_username();
+        if username.is_empty() {
+            return;
+        }
+
+        let config = self.config.clone();
+        let task_sender = task_sender.clone();
+        std::thread::spawn(move || {
+            let user_envs = crate::post_login::get_user_envs(&config, &username, &envs);
+            envs.extend(user_envs);
+
+            let event = TaskEvent::UserEnvironments {
+                username,
+                envs,
+                select,
+            };
+            if let Err(err) = task_sender.send(event) {
+                warn!("Failed to send task event. Reason: {}", err);
             }
+        });
+    }
+
+    /// Select the locale that was remembered for the currently entered user
+    fn refresh_user_locale(&self) {
+        if !self.config.locale_switcher.enabled || !self.config.locale_switcher.remember {
+            return;
         }
+
+        let username = self.widgets.get_username();
+        if username.is_empty() {
+            return;
+        }
+
+        if let Some(locale) = get_cached_information(&self.config).locale(&username) {
+            info!("Loading locale '{}' for '{}' from cache", locale, username);
+            self.widgets.locale_try_select(locale);
+            self.refresh_language();
+        }
+    }
+
+    /// Let the texts of the UI follow the language of the selected locale
+    fn refresh_language(&self) {
+        let locale = if self.config.locale_switcher.enabled {
+            self.widgets.get_locale()
+        } else {
+            None
+        };
+
+        messages::select_language(&self.config.messages, locale.as_deref());
     }
 
     pub fn new(config: Config, preview: bool) -> LoginForm {
@@ -285,6 +442,12 @@ impl LoginForm {
             preview,
             widgets: Widgets {
                 background: BackgroundWidget::new(config.background.clone()),
+                banner: BannerWidget::new(config.banner.clone()),
+                info_panel: InfoPanelWidget::new(config.info_panel.clone(), config.tty),
+                screensaver: ScreensaverWidget::new(
+                    config.screensaver.clone(),
+                    BannerWidget::new(config.banner.clone()),
+                ),
                 key_menu: KeyMenuWidget::new(
                     config.power_controls.clone(),
                     config.environment_switcher.clone(),
@@ -297,6 +460,17 @@ impl LoginForm {
                         .collect(),
                     config.environment_switcher.clone(),
                 ))),
+                locale: Arc::new(Mutex::new(SwitcherWidget::new(
+                    if config.locale_switcher.enabled {
+                        get_locales(&config.locale_switcher)
+                    } else {
+                        Vec::new()
+                    }
+                    .into_iter()
+                    .map(|locale| SwitcherItem::new(locale.clone(), locale))
+                    .collect(),
+                    locale_switcher_config(&config),
+                ))),
                 username: Arc::new(Mutex::new(InputFieldWidget::new(
                     InputFieldDisplayType::Echo,
                     config.username_field.style.clone(),
@@ -317,8 +491,228 @@ impl LoginForm {
         }
     }
 
-    pub fn run(self, terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> io::Result<()> {
-        self.load_cache();
+    /// Draw the login form to the terminal
+    fn draw(
+        &self,
+        terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+        form_layout: &FormLayout,
+        input_mode: InputMode,
+        status_message: Option<StatusMessage>,
+    ) -> io::Result<()> {
+        let locale = self
+            .config
+            .locale_switcher
+            .enabled
+            .then(|| self.widgets.locale.clone());
+
+        terminal.draw(|f| {
+            let layout = form_layout.chunks(f.size());
+            login_form_render(
+                f,
+                layout,
+                self.widgets.background.clone(),
+                &self.config.layout.border_color,
+                self.widgets.banner.clone(),
+                self.widgets.info_panel.clone(),
+                self.widgets.key_menu.clone(),
+                self.widgets.environment.clone(),
+                locale,
+                self.widgets.username.clone(),
+                self.widgets.password.clone(),
+                input_mode,
+                status_message,
+            );
+        })?;
+
+        Ok(())
+    }
+
+    fn draw_screensaver(
+        &self,
+        terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    ) -> io::Result<()> {
+        terminal.draw(|f| self.widgets.screensaver.render(f, f.size()))?;
+        Ok(())
+    }
+
+    /// Hide the login form behind the screensaver
+    fn start_screensaver(&self, reactor: &mut Reactor) {
+        info!("Starting the screensaver");
+
+        // Nobody should be able to continue with a password that was left behind
+        if self.widgets.screensaver.clears_password() {
+            self.widgets.clear_password();
+        }
+
+        if self.widgets.screensaver.blanks_console() {
+            if let Err(err) = crate::chvt::set_screen_blanked(true) {
+                warn!("Failed to blank the console. Reason: {}", err);
+            }
+        }
+
+        match self.widgets.screensaver.refresh_interval() {
+            Some(interval) => reactor.set_interval(Timer::Refresh, interval),
+            None => reactor.cancel_timer(Timer::Refresh),
+        }
+    }
+
+    /// Show the login form again
+    fn stop_screensaver(&self, reactor: &mut Reactor) {
+        info!("Stopping the screensaver");
+
+        if self.widgets.screensaver.blanks_console() {
+            if let Err(err) = crate::chvt::set_screen_blanked(false) {
+                warn!("Failed to unblank the console. Reason: {}", err);
+            }
+        }
+
+        match self.widgets.info_panel.refresh_interval() {
+            Some(interval) => reactor.set_interval(Timer::Refresh, interval),
+            None => reactor.cancel_timer(Timer::Refresh),
+        }
+    }
+
+    /// Attempt to log in with the entered credentials
+    ///
+    /// This blocks until the session has ended and is therefore run as a background task. The UI
+    /// is updated through the events sent to `task_sender`.
+    fn attempt_login(&self, status_message: &LoginFormStatusMessage, task_sender: &TaskSender) {
+        let send_task_event = |event: TaskEvent| {
+            if let Err(err) = task_sender.send(event) {
+                warn!("Failed to send task event. Reason: {}", err);
+            }
+        };
+
+        // The session may only take over the console once the UI has given it back. Otherwise,
+        // the console is restored while the session is already using it. A console font that
+        // cannot be restored only matters to sessions that run on the console.
+        let disable_tui = |uses_console: bool| {
+            let (done_sender, done_receiver) = channel();
+            send_task_event(TaskEvent::DisableTui(done_sender));
+
+            match done_receiver.recv() {
+                Ok(Ok(())) => Ok(()),
+                Ok(Err(err)) if uses_console => {
+                    error!("Failed to restore the console font. Reason: {err}");
+                    Err(EnvironmentStartError::ConsoleRestore)
+                }
+                Ok(Err(err)) => {
+                    warn!("Failed to restore the console font. Reason: {err}");
+                    Ok(())
+                }
+                Err(_) => {
+                    warn!("The UI stopped before it could disable the TUI");
+                    Ok(())
+                }
+            }
+        };
+        let enable_tui = || {
+            let (done_sender, done_receiver) = channel();
+            send_task_event(TaskEvent::EnableTui(done_sender));
+
+            if done_receiver.recv().is_err() {
+                warn!("The UI stopped before it could enable the TUI");
+            }
+        };
+
+        if self.preview {
+            // This is only for demonstration purposes
+            status_message.set(InfoStatusMessage::Authenticating);
+            send_task_event(TaskEvent::Redraw);
+            std::thread::sleep(Duration::from_secs(2));
+
+            status_message.set(InfoStatusMessage::LoggingIn);
+            send_task_event(TaskEvent::Redraw);
+            std::thread::sleep(Duration::from_secs(2));
+
+            status_message.clear();
+            send_task_event(TaskEvent::Redraw);
+            return;
+        }
+
+        let environment = self.widgets.get_environment();
+        let username = self.widgets.get_username();
+        let password = self.widgets.get_password();
+        let locale = if self.config.locale_switcher.enabled {
+            self.widgets.get_locale()
+        } else {
+            None
+        };
+
+        let Some((environment_name, post_login_env)) = environment else {
+            status_message.set(ErrorStatusMessage::NoGraphicalEnvironment);
+            send_task_event(TaskEvent::Redraw);
+            return;
+        };
+
+        let pre_auth = || {
+            self.widgets.clear_password();
+
+            status_message.set(InfoStatusMessage::Authenticating);
+            send_task_event(TaskEvent::Redraw);
+        };
+        let pre_environment = || {
+            // Remember username and environment for next time
+            self.set_cache();
+
+            status_message.set(InfoStatusMessage::LoggingIn);
+            send_task_event(TaskEvent::Redraw);
+
+            // Disable the rendering of the login manager
+            disable_tui(post_login_env.uses_console())
+        };
+        let pre_return = || {
+            // Enable the rendering of the login manager
+            enable_tui();
+
+            status_message.clear();
+            send_task_event(TaskEvent::Redraw);
+        };
+
+        let hooks = Hooks {
+            pre_validate: None,
+            pre_auth: Some(&pre_auth),
+            pre_environment: Some(&pre_environment),
+            pre_wait: None,
+            pre_return: Some(&pre_return),
+        };
+
+        match start_session(
+            &username,
+            &password,
+            &environment_name,
+            &post_login_env,
+            locale.as_deref(),
+            &hooks,
+            &self.config,
+        ) {
+            Ok(()) => {}
+            Err(StartSessionError::AuthenticationError(err)) => {
+                status_message.set(ErrorStatusMessage::AuthenticationError(err));
+                send_task_event(TaskEvent::Redraw);
+            }
+            Err(StartSessionError::EnvironmentStartError(err)) => {
+                error!("Starting post-login environment failed. Reason: '{}'", err);
+                enable_tui();
+
+                status_message.set(ErrorStatusMessage::FailedGraphicalEnvironment(err));
+                send_task_event(TaskEvent::Redraw);
+            }
+        }
+    }
+
+    pub fn run(mut self, terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> io::Result<()> {
+        // Start with the locale Lemurs itself runs in
+        if self.config.locale_switcher.enabled {
+            if let Ok(lang) = std::env::var("LANG") {
+                self.widgets.locale_try_select(&lang);
+            }
+        }
+
+        let mut reactor = Reactor::new()?;
+
+        self.load_cache(&reactor.task_sender());
+        self.refresh_language();
         let input_mode = LoginFormInputMode::new(match self.config.focus_behaviour {
             FocusBehaviour::FirstNonCached => match (
                 self.config.username_field.remember && !self.widgets.get_username().is_empty(),
@@ -347,138 +741,88 @@ impl LoginForm {
             FocusBehaviour::Password => InputMode::Password,
         });
         let status_message = LoginFormStatusMessage::new();
-        let background = self.widgets.background.clone();
-        let key_menu = self.widgets.key_menu.clone();
-        let environment = self.widgets.environment.clone();
-        let username = self.widgets.username.clone();
-        let password = self.widgets.password.clone();
-
-        let draw_action = terminal.draw(|f| {
-            let layout = Chunks::new(f);
-            login_form_render(
-                f,
-                layout,
-                background.clone(),
-                key_menu.clone(),
-                environment.clone(),
-                username.clone(),
-                password.clone(),
-                input_mode.get(),
-                status_message.get(),
-            );
-        });
 
-        if let Err(err) = draw_action {
+        // Let the Linux console show the colors of the theme as they are
+        let console_palette = if self.config.theme.program_console_palette {
+            let colors: Vec<(u8, u8, u8)> = self
+                .config
+                .theme
+                .console_palette
+                .iter()
+                .filter_map(|color| resolve_color(color).and_then(color::to_rgb))
+                .collect();
+            ConsolePalette::program(&colors)
+        } else {
+            None
+        };
+        let console_font = ConsoleFont::load(&self.config.console);
+
+        let form_layout = FormLayout::new(
+            &self.config,
+            self.widgets.banner.height(),
+            self.widgets.info_panel.height(),
+        );
+
+        if let Err(err) = self.draw(terminal, &form_layout, input_mode.get(), None) {
             error!("Failed to draw. Reason: {}", err);
             std::process::exit(1);
         }
 
-        let event_input_mode = input_mode.clone();
-        let event_status_message = status_message.clone();
+        // Widgets that change over time need to be redrawn without any key presses
+        if let Some(interval) = self.widgets.info_panel.refresh_interval() {
+            reactor.set_interval(Timer::Refresh, interval);
+        }
 
-        let (req_send_channel, req_recv_channel) = channel();
-        std::thread::spawn(move || {
-            let mut switcher_hidden = self
-                .widgets
-                .environment
-                .lock()
-                .expect("Failed to grab environment lock")
-                .hidden();
-            let input_mode = event_input_mode;
-            let status_message = event_status_message;
-
-            let send_ui_request = |request: UIThreadRequest| match req_send_channel.send(request) {
-                Ok(_) => {}
-                Err(err) => warn!("Failed to send UI request. Reason: {}", err),
-            };
+        let error_timeout = (self.config.status_message.error_timeout_secs != 0)
+            .then(|| Duration::from_secs(self.config.status_message.error_timeout_secs.into()));
 
-            let pre_auth = || {
-                self.widgets.clear_password();
+        let idle_timeout = self.widgets.screensaver.idle_timeout();
+        if let Some(timeout) = idle_timeout {
+            reactor.set_timer(Timer::Idle, timeout);
+        }
 
-                status_message.set(InfoStatusMessage::Authenticating);
-                send_ui_request(UIThreadRequest::Redraw);
-            };
-            let pre_environment = || {
-                // Remember username and environment for next time
-                self.set_cache();
+        let mut switcher_hidden = self.widgets.environment_guard().hidden();
+        let locale_hidden = !self.config.locale_switcher.enabled;
+        let mut is_tui_enabled = true;
+        let mut idle_state = IdleState::default();
 
-                status_message.set(InfoStatusMessage::LoggingIn);
-                send_ui_request(UIThreadRequest::Redraw);
+        loop {
+            let needs_redraw = match reactor.next()? {
+                // The key that wakes up the screen should not be typed into the form
+                ReactorEvent::Terminal(Event::Key(_)) if !idle_state.take_key() => {
+                    self.stop_screensaver(&mut reactor);
+                    self.widgets.info_panel.refresh();
 
-                // Disable the rendering of the login manager
-                send_ui_request(UIThreadRequest::DisableTui);
-            };
-            let pre_return = || {
-                // Enable the rendering of the login manager
-                send_ui_request(UIThreadRequest::EnableTui);
-
-                status_message.clear();
-                send_ui_request(UIThreadRequest::Redraw);
-            };
+                    if let Some(timeout) = idle_timeout {
+                        reactor.set_timer(Timer::Idle, timeout);
+                    }
+                    if let Err(err) = terminal.clear() {
+                        warn!("Failed to clear the screen. Reason: {err}");
+                    }
+                    true
+                }
+                ReactorEvent::Terminal(Event::Key(key)) => {
+                    if let Some(timeout) = idle_timeout {
+                        reactor.set_timer(Timer::Idle, timeout);
+                    }
 
-            let hooks = Hooks {
-                pre_validate: None,
-                pre_auth: Some(&pre_auth),
-                pre_environment: Some(&pre_environment),
-                pre_wait: None,
-                pre_return: Some(&pre_return),
-            };
+                    let previous_input_mode = input_mode.get();
 
-            loop {
-                if let Ok(Event::Key(key)) = event::read() {
                     match (key.code, input_mode.get(), key.modifiers) {
                         (KeyCode::Enter, InputMode::Password, _) => {
-                            if self.preview {
-                                // This is only for demonstration purposes
-                                status_message.set(InfoStatusMessage::Authenticating);
-                                send_ui_request(UIThreadRequest::Redraw);
-                                std::thread::sleep(Duration::from_secs(2));
-
-                                status_message.set(InfoStatusMessage::LoggingIn);
-                                send_ui_request(UIThreadRequest::Redraw);
-                                std::thread::sleep(Duration::from_secs(2));
-
-                                status_message.clear();
-                                send_ui_request(UIThreadRequest::Redraw);
-                            } else {
-                                let environment =
-                                    self.widgets.get_environment().map(|(_, content)| content);
-                                let username = self.widgets.get_username();
-                                let password = self.widgets.get_password();
-                                let config = self.config.clone();
-
-                                let Some(post_login_env) = environment else {
-                                    status_message.set(ErrorStatusMessage::NoGraphicalEnvironment);
-                                    send_ui_request(UIThreadRequest::Redraw);
-                                    continue;
-                                };
-
-                                match start_session(
-                                    &username,
-                                    &password,
-                                    &post_login_env,
-                                    &hooks,
-                                    &config,
-                                ) {
-                                    Ok(()) => {}
-                                    Err(StartSessionError::AuthenticationError(err)) => {
-                                        status_message
-                                            .set(ErrorStatusMessage::AuthenticationError(err));
-                                        send_ui_request(UIThreadRequest::Redraw);
-                                    }
-                                    Err(StartSessionError::EnvironmentStartError(err)) => {
-                                        error!(
-                                            "Starting post-login environment failed. Reason: '{}'",
-                                            err
-                                        );
-                                        send_ui_request(UIThreadRequest::EnableTui);
-
-                                        status_message
-                                            .set(ErrorStatusMessage::FailedGraphicalEnvironment);
-                                        send_ui_request(UIThreadRequest::Redraw);
-                                    }
+                            // No input should be taken from the terminal while logging in
+                            reactor.set_reads_terminal(false);
+
+                            let login_form = self.clone();
+                            let status_message = status_message.clone();
+                            let task_sender = reactor.task_sender();
+                            std::thread::spawn(move || {
+                                login_form.attempt_login(&status_message, &task_sender);
+
+                                if let Err(err) = task_sender.send(TaskEvent::Finished) {
+                                    warn!("Failed to send task event. Reason: {}", err);
                                 }
-                            }
+                            });
                         }
                         (KeyCode::Char('s'), InputMode::Normal, _) => self.set_cache(),
 
@@ -486,19 +830,19 @@ impl LoginForm {
                         (KeyCode::Up | KeyCode::BackTab, _, _)
                         | (KeyCode::Tab, _, KeyModifiers::ALT | KeyModifiers::SHIFT)
                         | (KeyCode::Char('p'), _, KeyModifiers::CONTROL) => {
-                            input_mode.prev(switcher_hidden);
+                            input_mode.prev(switcher_hidden, locale_hidden);
                         }
 
                         (KeyCode::Enter | KeyCode::Down | KeyCode::Tab, _, _)
                         | (KeyCode::Char('n'), _, KeyModifiers::CONTROL) => {
-                            input_mode.next(switcher_hidden);
+                            input_mode.next(switcher_hidden, locale_hidden);
                         }
 
                         // Esc is the overal key to get out of your input mode
                         (KeyCode::Esc, InputMode::Normal, _) => {
                             if self.preview {
                                 info!("Pressed escape in preview mode to exit the application");
-                                req_send_channel.send(UIThreadRequest::StopDrawing).unwrap();
+                                break;
                             }
                         }
 
@@ -510,15 +854,10 @@ impl LoginForm {
                             self.widgets.key_menu.key_press(key.code);
                             self.widgets.environment_guard().key_press(key.code);
 
-                            switcher_hidden = self
-                                .widgets
-                                .environment
-                                .lock()
-                                .expect("Failed to grab lock")
-                                .hidden();
+                            switcher_hidden = self.widgets.environment_guard().hidden();
 
                             if matches!(input_mode.get(), InputMode::Switcher) && switcher_hidden {
-                                input_mode.next(true);
+                                input_mode.next(true, locale_hidden);
                             }
                         }
 
@@ -529,6 +868,11 @@ impl LoginForm {
                                 InputMode::Switcher => {
                                     self.widgets.environment_guard().key_press(k)
                                 }
+                                InputMode::Locale => {
+                                    let status_message = self.widgets.locale_guard().key_press(k);
+                                    self.refresh_language();
+                                    status_message
+                                }
                                 InputMode::Username => {
                                     self.widgets.username_guard().key_press(k, modifiers)
                                 }
@@ -544,38 +888,40 @@ impl LoginForm {
                             }
                         }
                     };
-                }
 
-                send_ui_request(UIThreadRequest::Redraw);
-            }
-        });
+                    // The user might have their own environments once the username is entered
+                    if matches!(previous_input_mode, InputMode::Username)
+                        && !matches!(input_mode.get(), InputMode::Username)
+                    {
+                        self.refresh_user_environments(&reactor.task_sender(), None);
+                        self.refresh_user_locale();
+                    }
 
-        // Start the UI thread. This actually draws to the screen.
-        //
-        // This blocks until we actually call StopDrawing
-        while let Ok(request) = req_recv_channel.recv() {
-            match request {
-                UIThreadRequest::Redraw => {
-                    let draw_action = terminal.draw(|f| {
-                        let layout = Chunks::new(f);
-                        login_form_render(
-                            f,
-                            layout,
-                            background.clone(),
-                            key_menu.clone(),
-                            environment.clone(),
-                            username.clone(),
-                            password.clone(),
-                            input_mode.get(),
-                            status_message.get(),
-                        );
-                    });
-
-                    if let Err(err) = draw_action {
-                        warn!("Failed to draw to screen. Reason: {err}");
+                    true
+                }
+                ReactorEvent::Terminal(Event::Resize(_, _)) => {
+                    // After a font or console mode change, the old contents of the screen no
+                    // longer line up with the new size
+                    if is_tui_enabled {
+                        if let Err(err) = terminal.clear() {
+                            warn!("Failed to clear the screen after a resize. Reason: {err}");
+                        }
                     }
+                    true
                 }
-                UIThreadRequest::DisableTui => {
+                ReactorEvent::Terminal(_) => false,
+                ReactorEvent::Task(TaskEvent::Redraw) => true,
+                ReactorEvent::Task(TaskEvent::DisableTui(done_sender)) => {
+                    is_tui_enabled = false;
+                    // Nothing is drawn while the session runs
+                    reactor.cancel_timer(Timer::Refresh);
+                    if let Some(console_palette) = &console_palette {
+                        console_palette.restore();
+                    }
+                    let restored = match &console_font {
+                        Some(console_font) => console_font.restore(),
+                        None => Ok(()),
+                    };
                     disable_raw_mode()?;
                     execute!(
                         terminal.backend_mut(),
@@ -584,14 +930,105 @@ impl LoginForm {
                         MoveTo(0, 0)
                     )?;
                     terminal.show_cursor()?;
+                    let _ = done_sender.send(restored);
+                    false
                 }
-                UIThreadRequest::EnableTui => {
+                ReactorEvent::Task(TaskEvent::EnableTui(done_sender)) => {
+                    is_tui_enabled = true;
+                    if let Some(console_palette) = &console_palette {
+                        console_palette.apply();
+                    }
+                    if let Some(console_font) = &console_font {
+                        console_font.apply();
+                    }
                     enable_raw_mode()?;
                     let mut stdout = io::stdout();
                     execute!(stdout, EnterAlternateScreen)?;
                     terminal.clear()?;
+                    self.widgets.info_panel.refresh();
+                    if let Some(interval) = self.widgets.info_panel.refresh_interval() {
+                        reactor.set_interval(Timer::Refresh, interval);
+                    }
+                    let _ = done_sender.send(());
+                    true
+                }
+                // The username might have changed while the environments were searched for
+                ReactorEvent::Task(TaskEvent::UserEnvironments {
+                    username,
+                    envs,
+                    select,
+                }) => {
+                    if username == self.widgets.get_username() {
+                        self.widgets.set_environments(envs);
+                        if let Some(env) = select {
+                            self.widgets.environment_try_select(&env);
+                        }
+                        switcher_hidden = self.widgets.environment_guard().hidden();
+                        true
+                    } else {
+                        false
+                    }
+                }
+                ReactorEvent::Task(TaskEvent::Finished) => {
+                    reactor.set_reads_terminal(true);
+
+                    if let Some(timeout) = idle_timeout {
+                        reactor.set_timer(Timer::Idle, timeout);
+                    }
+                    true
                 }
-                _ => break,
+                // The screensaver uses the same timer, but only the info panel caches its content
+                ReactorEvent::Timer(Timer::Refresh) => {
+                    if !idle_state.is_idle() {
+                        self.widgets.info_panel.refresh();
+                    }
+                    true
+                }
+                ReactorEvent::Timer(Timer::StatusMessageExpiry) => {
+                    if let Some(timeout) = error_timeout {
+                        status_message.clear_expired_error(timeout);
+                    }
+                    true
+                }
+                // Logging in does not count as being idle. The timer is restarted once it is done.
+                ReactorEvent::Timer(Timer::Idle) if !reactor.reads_terminal() => false,
+                ReactorEvent::Timer(Timer::Idle) => {
+                    idle_state.start_idling();
+                    self.start_screensaver(&mut reactor);
+                    true
+                }
+            };
+
+            if needs_redraw && is_tui_enabled {
+                let draw_action = if idle_state.is_idle() {
+                    self.draw_screensaver(terminal)
+                } else {
+                    self.draw(
+                        terminal,
+                        &form_layout,
+                        input_mode.get(),
+                        status_message.get(),
+                    )
+                };
+
+                if let Err(err) = draw_action {
+                    warn!("Failed to draw to screen. Reason: {err}");
+                }
+            }
+
+            // Error messages are cleared once they have been shown for long enough
+            match error_timeout.and_then(|timeout| status_message.error_expiry(timeout)) {
+                Some(expiry) => reactor.set_timer(Timer::StatusMessageExpiry, expiry),
+                None => reactor.cancel_timer(Timer::StatusMessageExpiry),
+            }
+        }
+
+        if let Some(console_palette) = &console_palette {
+            console_palette.restore();
+        }
+        if let Some(console_font) = &console_font {
+            if let Err(err) = console_font.restore() {
+                warn!("Failed to restore the font of the console. Reason: {err}");
             }
         }
 
@@ -599,20 +1036,46 @@ impl LoginForm {
     }
 }
 
+/// The locale switcher is always visible and otherwise styled like the environment switcher
+fn locale_switcher_config(config: &Config) -> SwitcherConfig {
+    SwitcherConfig {
+        switcher_visibility: SwitcherVisibility::Visible,
+        include_tty_shell: false,
+        remember: config.locale_switcher.remember,
+        max_display_length: config.locale_switcher.max_display_length,
+        no_envs_text: config.locale_switcher.no_locales_text.clone(),
+        ..config.environment_switcher.clone()
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 fn login_form_render<B: Backend>(
     frame: &mut Frame<B>,
     chunks: Chunks,
     background: BackgroundWidget,
+    border_color: &str,
+    banner: BannerWidget,
+    info_panel: InfoPanelWidget,
     key_menu: KeyMenuWidget,
     environment: Arc<Mutex<SwitcherWidget<PostLoginEnvironment>>>,
+    locale: Option<Arc<Mutex<SwitcherWidget<String>>>>,
     username: Arc<Mutex<InputFieldWidget>>,
     password: Arc<Mutex<InputFieldWidget>>,
     input_mode: InputMode,
     status_message: Option<StatusMessage>,
 ) {
     background.render(frame);
+    if let Some(form_border) = chunks.form_border {
+        frame.render_widget(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(get_color(border_color))),
+            form_border,
+        );
+    }
     key_menu.render(frame, chunks.key_menu);
+    banner.render(frame, chunks.banner);
+    info_panel.render(frame, chunks.info_panel);
     environment
         .lock()
         .unwrap_or_else(|err| {
@@ -624,6 +1087,19 @@ fn login_form_render<B: Backend>(
             chunks.switcher,
             matches!(input_mode, InputMode::Switcher),
         );
+    if let Some(locale) = locale {
+        locale
+            .lock()
+            .unwrap_or_else(|err| {
+                error!("Failed to lock locale. Reason: {}", err);
+                std::process::exit(1);
+            })
+            .render(
+                frame,
+                chunks.locale_switcher,
+                matches!(input_mode, InputMode::Locale),
+            );
+    }
     username
         .lock()
         .unwrap_or_else(|err| {
@@ -650,3 +1126,54 @@ fn login_form_render<B: Backend>(
     // Display Status Message
     StatusMessage::render(status_message, frame, chunks.status_message);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::messages::{message, Message};
+    use super::reactor::Reactor;
+    use super::LoginForm;
+    use crate::config::Config;
+
+    use std::fs;
+
+    #[test]
+    fn cached_user_locale_selects_language_at_startup() {
+        let dir = std::env::temp_dir().join(format!("lemurs-ui-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        fs::write(
+            dir.join("nl.toml"),
+            "[messages]\nauthentication_failed = \"Authenticatie mislukt!\"\n",
+        )
+        .unwrap();
+        fs::write(dir.join("cache"), "\nalice\nalice=nl_NL.UTF-8\n").unwrap();
+
+        let mut config = Config {
+            cache_path: dir.join("cache").to_string_lossy().to_string(),
+            ..Config::default()
+        };
+        config.user_sessions.enabled = false;
+        config.username_field.remember = true;
+        config.locale_switcher.enabled = true;
+        config.locale_switcher.remember = true;
+        config.locale_switcher.locales = vec!["en_US.UTF-8".into(), "nl_NL.UTF-8".into()];
+        config.messages.language = String::new();
+        config.messages.catalogs_path = dir.to_string_lossy().to_string();
+
+        let login_form = LoginForm::new(config, true);
+        let reactor = Reactor::new().unwrap();
+        login_form.load_cache(&reactor.task_sender());
+
+        assert_eq!(
+            login_form.widgets.get_locale().as_deref(),
+            Some("nl_NL.UTF-8")
+        );
+        assert_eq!(
+            message(Message::AuthenticationFailed),
+            "Authenticatie mislukt!"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,416 @@
+//! This module implements the event reactor of the UI.
+//!
+//! The reactor multiplexes the events of the terminal, timers and the events sent by background
+//! tasks (e.g. a login attempt) into a single stream of [`ReactorEvent`]s. crossterm cannot wait on
+//! a channel, so the reactor blocks on a `mio` poll of its own until the next timer expires. The
+//! poll is woken up by the [`TaskSender`], when the terminal becomes readable and on SIGWINCH.
+//! crossterm is then asked for the events it has read without blocking.
+
+use std::fs::File;
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::mpsc::{channel, Receiver, SendError, Sender};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crossterm::event::{self, Event};
+use log::warn;
+use mio::unix::SourceFd;
+use mio::{Events, Interest, Poll, Token, Waker};
+use signal_hook_mio::v0_8::Signals;
+
+use crate::chvt::ChvtError;
+use crate::post_login::PostLoginEnvironment;
+
+const TASK_TOKEN: Token = Token(0);
+const TERMINAL_TOKEN: Token = Token(1);
+const RESIZE_TOKEN: Token = Token(2);
+
+/// The events that are sent by background tasks
+pub enum TaskEvent {
+    Redraw,
+    /// Stop drawing and give the console back in the state it was in before Lemurs changed it.
+    /// Whether the console could be restored is sent back once this is done.
+    DisableTui(Sender<Result<(), ChvtError>>),
+    /// Take over the console again. The sender is notified once this is done.
+    EnableTui(Sender<()>),
+    /// The background task has finished and terminal input can be handled again
+    Finished,
+    /// The environments that were found in the home directory of `username`, together with the
+    /// environment to select once they are shown
+    UserEnvironments {
+        username: String,
+        envs: Vec<(String, PostLoginEnvironment)>,
+        select: Option<String>,
+    },
+}
+
+/// The timers that can be set on the reactor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timer {
+    /// Redraw widgets that change over time
+    Refresh,
+    /// Clear the status message once it expired
+    StatusMessageExpiry,
+    /// Start the screensaver after no key was pressed for a while
+    Idle,
+}
+
+pub enum ReactorEvent {
+    Terminal(Event),
+    Task(TaskEvent),
+    Timer(Timer),
+}
+
+struct ScheduledTimer {
+    timer: Timer,
+    deadline: Instant,
+    interval: Option<Duration>,
+}
+
+/// Sends events of background tasks to the reactor and wakes it up
+#[derive(Clone)]
+pub struct TaskSender {
+    sender: Sender<TaskEvent>,
+    waker: Arc<Waker>,
+}
+
+impl TaskSender {
+    pub fn send(&self, event: TaskEvent) -> Result<(), SendError<TaskEvent>> {
+        self.sender.send(event)?;
+
+        // The event is still received once the reactor wakes up for another reason
+        if let Err(err) = self.waker.wake() {
+            warn!("Failed to wake up the reactor. Reason: {err}");
+        }
+
+        Ok(())
+    }
+}
+
+/// The terminal that crossterm reads its events from
+///
+/// crossterm uses stdin if it is a terminal and opens `/dev/tty` otherwise.
+enum TerminalInput {
+    Stdin,
+    Tty(File),
+}
+
+impl TerminalInput {
+    fn open() -> io::Result<Self> {
+        if unsafe { libc::isatty(libc::STDIN_FILENO) } == 1 {
+            Ok(Self::Stdin)
+        } else {
+            File::open("/dev/tty").map(Self::Tty)
+        }
+    }
+
+    fn as_raw_fd(&self) -> RawFd {
+        match self {
+            Self::Stdin => libc::STDIN_FILENO,
+            Self::Tty(file) => file.as_raw_fd(),
+        }
+    }
+}
+
+pub struct Reactor {
+    sender: TaskSender,
+    receiver: Receiver<TaskEvent>,
+    timers: Vec<ScheduledTimer>,
+    reads_terminal: bool,
+    poll: Poll,
+    events: Events,
+    /// The terminal while it is registered with the poll
+    terminal: Option<TerminalInput>,
+    resize_signals: Signals,
+}
+
+impl Reactor {
+    pub fn new() -> io::Result<Self> {
+        let (sender, receiver) = channel();
+
+        let poll = Poll::new()?;
+        let waker = Arc::new(Waker::new(poll.registry(), TASK_TOKEN)?);
+
+        let mut resize_signals = Signals::new([libc::SIGWINCH])?;
+        poll.registry()
+            .register(&mut resize_signals, RESIZE_TOKEN, Interest::READABLE)?;
+
+        Ok(Self {
+            sender: TaskSender { sender, waker },
+            receiver,
+            timers: Vec::new(),
+            reads_terminal: true,
+            poll,
+            events: Events::with_capacity(4),
+            terminal: None,
+            resize_signals,
+        })
+    }
+
+    /// Get a sender for background tasks to send events to the reactor
+    pub fn task_sender(&self) -> TaskSender {
+        self.sender.clone()
+    }
+
+    /// Set whether terminal events should be read
+    ///
+    /// This should be disabled while a session is using the terminal, so that no input is taken
+    /// from it.
+    pub fn set_reads_terminal(&mut self, reads_terminal: bool) {
+        self.reads_terminal = reads_terminal;
+    }
+
+    pub fn reads_terminal(&self) -> bool {
+        self.reads_terminal
+    }
+
+    /// Only wake up for terminal input while terminal events are read
+    ///
+    /// Otherwise, every key press within a session that uses the terminal would wake the reactor.
+    fn update_terminal_registration(&mut self) -> io::Result<()> {
+        match (self.reads_terminal, &self.terminal) {
+            (true, None) => {
+                let terminal = TerminalInput::open()?;
+                self.poll.registry().register(
+                    &mut SourceFd(&terminal.as_raw_fd()),
+                    TERMINAL_TOKEN,
+                    Interest::READABLE,
+                )?;
+                self.terminal = Some(terminal);
+            }
+            (false, Some(terminal)) => {
+                self.poll
+                    .registry()
+                    .deregister(&mut SourceFd(&terminal.as_raw_fd()))?;
+                self.terminal = None;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn schedule(&mut self, timer: Timer, after: Duration, interval: Option<Duration>) {
+        self.cancel_timer(timer);
+        self.timers.push(ScheduledTimer {
+            timer,
+            deadline: Instant::now() + after,
+            interval,
+        });
+    }
+
+    /// Fire `timer` once after `after`. This replaces a previous schedule of the same timer.
+    pub fn set_timer(&mut self, timer: Timer, after: Duration) {
+        self.schedule(timer, after, None);
+    }
+
+    /// Fire `timer` every `interval`. This replaces a previous schedule of the same timer.
+    pub fn set_interval(&mut self, timer: Timer, interval: Duration) {
+        self.schedule(timer, interval, Some(interval));
+    }
+
+    pub fn cancel_timer(&mut self, timer: Timer) {
+        self.timers.retain(|scheduled| scheduled.timer != timer);
+    }
+
+    /// Take the timer that expired first
+    fn expired_timer(&mut self, now: Instant) -> Option<Timer> {
+        let (index, _) = self
+            .timers
+            .iter()
+            .enumerate()
+            .filter(|(_, scheduled)| scheduled.deadline <= now)
+            .min_by_key(|(_, scheduled)| scheduled.deadline)?;
+
+        let scheduled = &mut self.timers[index];
+        let timer = scheduled.timer;
+
+        match scheduled.interval {
+            // Skip the intervals that were missed
+            Some(interval) => {
+                while scheduled.deadline <= now {
+                    scheduled.deadline += interval;
+                }
+            }
+            None => {
+                self.timers.remove(index);
+            }
+        }
+
+        Some(timer)
+    }
+
+    /// Wait for the next event
+    pub fn next(&mut self) -> io::Result<ReactorEvent> {
+        self.update_terminal_registration()?;
+
+        loop {
+            if let Ok(task_event) = self.receiver.try_recv() {
+                return Ok(ReactorEvent::Task(task_event));
+            }
+
+            let now = Instant::now();
+            if let Some(timer) = self.expired_timer(now) {
+                return Ok(ReactorEvent::Timer(timer));
+            }
+
+            // crossterm may still hold events that it has already read from the terminal
+            if self.reads_terminal && event::poll(Duration::ZERO)? {
+                return Ok(ReactorEvent::Terminal(event::read()?));
+            }
+
+            let timeout = self
+                .timers
+                .iter()
+                .map(|scheduled| scheduled.deadline - now)
+                .min();
+
+            if let Err(err) = self.poll.poll(&mut self.events, timeout) {
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+
+            // crossterm receives SIGWINCH itself. This only needs to wake up the reactor.
+            if self
+                .events
+                .iter()
+                .any(|event| event.token() == RESIZE_TOKEN)
+            {
+                self.resize_signals.pending().for_each(drop);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reactor() -> Reactor {
+        let mut reactor = Reactor::new().unwrap();
+        reactor.set_reads_terminal(false);
+        reactor
+    }
+
+    fn millis(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn intervals_keep_firing() {
+        let mut reactor = reactor();
+        reactor.set_interval(Timer::Refresh, millis(10));
+        let now = Instant::now();
+
+        assert_eq!(
+            reactor.expired_timer(now + millis(10)),
+            Some(Timer::Refresh)
+        );
+        assert_eq!(reactor.expired_timer(now + millis(10)), None);
+        assert_eq!(
+            reactor.expired_timer(now + millis(20)),
+            Some(Timer::Refresh)
+        );
+    }
+
+    #[test]
+    fn missed_intervals_are_skipped() {
+        let mut reactor = reactor();
+        reactor.set_interval(Timer::Refresh, millis(10));
+        let now = Instant::now();
+
+        assert_eq!(
+            reactor.expired_timer(now + millis(55)),
+            Some(Timer::Refresh)
+        );
+        assert_eq!(reactor.expired_timer(now + millis(55)), None);
+    }
+
+    #[test]
+    fn single_timers_fire_once() {
+        let mut reactor = reactor();
+        reactor.set_timer(Timer::StatusMessageExpiry, millis(10));
+        let now = Instant::now();
+
+        assert_eq!(
+            reactor.expired_timer(now + millis(10)),
+            Some(Timer::StatusMessageExpiry)
+        );
+        assert_eq!(reactor.expired_timer(now + millis(100)), None);
+        assert!(reactor.timers.is_empty());
+    }
+
+    #[test]
+    fn earliest_timer_fires_first() {
+        let mut reactor = reactor();
+        reactor.set_timer(Timer::StatusMessageExpiry, millis(20));
+        reactor.set_interval(Timer::Refresh, millis(10));
+        let now = Instant::now();
+
+        assert_eq!(
+            reactor.expired_timer(now + millis(30)),
+            Some(Timer::Refresh)
+        );
+        assert_eq!(
+            reactor.expired_timer(now + millis(30)),
+            Some(Timer::StatusMessageExpiry)
+        );
+    }
+
+    #[test]
+    fn setting_a_timer_replaces_its_schedule() {
+        let mut reactor = reactor();
+        reactor.set_timer(Timer::Idle, millis(10));
+        reactor.set_timer(Timer::Idle, Duration::from_secs(60));
+        let now = Instant::now();
+
+        assert_eq!(reactor.expired_timer(now + millis(100)), None);
+        assert_eq!(reactor.timers.len(), 1);
+    }
+
+    #[test]
+    fn cancelled_timers_do_not_fire() {
+        let mut reactor = reactor();
+        reactor.set_interval(Timer::Refresh, millis(10));
+        reactor.cancel_timer(Timer::Refresh);
+
+        assert_eq!(reactor.expired_timer(Instant::now() + millis(100)), None);
+        assert!(reactor.timers.is_empty());
+    }
+
+    #[test]
+    fn task_events_come_before_expired_timers() {
+        let mut reactor = reactor();
+        reactor.set_timer(Timer::Idle, Duration::ZERO);
+        reactor.task_sender().send(TaskEvent::Finished).unwrap();
+
+        assert!(matches!(
+            reactor.next().unwrap(),
+            ReactorEvent::Task(TaskEvent::Finished)
+        ));
+        assert!(matches!(
+            reactor.next().unwrap(),
+            ReactorEvent::Timer(Timer::Idle)
+        ));
+    }
+
+    #[test]
+    fn task_events_wake_up_the_reactor() {
+        let mut reactor = reactor();
+
+        // Without any timers, only the task sender can wake up the reactor
+        let task_sender = reactor.task_sender();
+        let sender_thread = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            task_sender.send(TaskEvent::Redraw).unwrap();
+        });
+
+        assert!(matches!(
+            reactor.next().unwrap(),
+            ReactorEvent::Task(TaskEvent::Redraw)
+        ));
+        sender_thread.join().unwrap();
+    }
+}
@@ -0,0 +1,174 @@
+//! This module implements the screensaver that hides the login form after being idle.
+//!
+//! The clock or banner moves around the screen to prevent burn-in on consoles that show the login
+//! screen for a long time.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use ratatui::{layout::Rect, style::Style, widgets::Paragraph, Frame};
+use unicode_width::UnicodeWidthStr;
+
+use crate::config::{get_color, ScreensaverConfig, ScreensaverMode};
+
+use super::banner::BannerWidget;
+use super::info_panel::format_time;
+
+/// How often the screensaver needs to be redrawn for the clock to stay up-to-date
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Whether the screensaver is shown instead of the login form
+#[derive(Default)]
+pub struct IdleState {
+    is_idle: bool,
+}
+
+impl IdleState {
+    pub fn is_idle(&self) -> bool {
+        self.is_idle
+    }
+
+    pub fn start_idling(&mut self) {
+        self.is_idle = true;
+    }
+
+    /// Take a key press and return whether it is meant for the login form
+    ///
+    /// The key that wakes up the screen is swallowed, so that it is not typed into the form.
+    pub fn take_key(&mut self) -> bool {
+        !std::mem::take(&mut self.is_idle)
+    }
+}
+
+#[derive(Clone)]
+pub struct ScreensaverWidget {
+    config: ScreensaverConfig,
+    banner: BannerWidget,
+}
+
+/// Get the place of content of `width` x `height` within `area` for the `step`-th move
+///
+/// The place jumps around pseudo-randomly, so that no part of the screen is lit for long.
+fn content_area(area: Rect, width: u16, height: u16, step: u64) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+
+    let hash = step
+        .wrapping_mul(6364136223846793005)
+        .wrapping_add(1442695040888963407);
+
+    let free_width = u64::from(area.width - width) + 1;
+    let free_height = u64::from(area.height - height) + 1;
+
+    Rect::new(
+        area.x + ((hash >> 33) % free_width) as u16,
+        area.y + ((hash >> 17) % free_height) as u16,
+        width,
+        height,
+    )
+}
+
+impl ScreensaverWidget {
+    pub fn new(config: ScreensaverConfig, banner: BannerWidget) -> Self {
+        Self { config, banner }
+    }
+
+    /// The time without key presses after which the screensaver starts
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        (self.config.idle_timeout_secs != 0)
+            .then(|| Duration::from_secs(self.config.idle_timeout_secs.into()))
+    }
+
+    /// How often the screensaver needs to be redrawn
+    pub fn refresh_interval(&self) -> Option<Duration> {
+        (self.config.mode != ScreensaverMode::Blank).then_some(REFRESH_INTERVAL)
+    }
+
+    pub fn blanks_console(&self) -> bool {
+        self.config.blank_console
+    }
+
+    pub fn clears_password(&self) -> bool {
+        self.config.clear_password
+    }
+
+    /// The number of moves the content has made since the epoch
+    fn step(&self) -> u64 {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+
+        seconds
+            .checked_div(self.config.move_interval_secs.into())
+            .unwrap_or(0)
+    }
+
+    pub fn render(&self, frame: &mut Frame<impl ratatui::backend::Backend>, area: Rect) {
+        let show_banner = self.config.mode == ScreensaverMode::Banner && self.banner.height() != 0;
+
+        if show_banner {
+            let banner_area =
+                content_area(area, self.banner.width(), self.banner.height(), self.step());
+            self.banner.render(frame, banner_area);
+        } else if self.config.mode != ScreensaverMode::Blank {
+            let time = format_time(&self.config.time_format);
+            let time_width = u16::try_from(time.width()).unwrap_or(u16::MAX);
+
+            let clock_area = content_area(area, time_width, 1, self.step());
+            frame.render_widget(
+                Paragraph::new(time).style(Style::default().fg(get_color(&self.config.color))),
+                clock_area,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_stays_on_screen() {
+        let area = Rect::new(3, 2, 40, 10);
+
+        for step in 0..100 {
+            for (width, height) in [(0, 0), (5, 1), (40, 10), (60, 20)] {
+                let content = content_area(area, width, height, step);
+                assert_eq!(area.union(content), area);
+            }
+        }
+    }
+
+    #[test]
+    fn content_moves_between_steps() {
+        let area = Rect::new(3, 2, 40, 10);
+
+        assert_ne!(content_area(area, 5, 1, 0), content_area(area, 5, 1, 1));
+    }
+
+    #[test]
+    fn keys_reach_the_form_when_not_idle() {
+        let mut idle_state = IdleState::default();
+
+        assert!(idle_state.take_key());
+        assert!(idle_state.take_key());
+    }
+
+    #[test]
+    fn waking_key_is_swallowed() {
+        let mut idle_state = IdleState::default();
+        idle_state.start_idling();
+
+        assert!(!idle_state.take_key());
+        assert!(!idle_state.is_idle());
+    }
+
+    #[test]
+    fn keys_after_waking_up_reach_the_form() {
+        let mut idle_state = IdleState::default();
+        idle_state.start_idling();
+        idle_state.take_key();
+
+        assert!(idle_state.take_key());
+    }
+}
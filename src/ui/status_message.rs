@@ -5,12 +5,15 @@ use ratatui::widgets::Paragraph;
 use ratatui::Frame;
 
 use crate::auth::AuthenticationError;
+use crate::post_login::EnvironmentStartError;
+
+use super::messages::{message, message_with, text, Message};
 
 #[derive(Clone)]
 pub enum ErrorStatusMessage {
     AuthenticationError(AuthenticationError),
     NoGraphicalEnvironment,
-    FailedGraphicalEnvironment,
+    FailedGraphicalEnvironment(EnvironmentStartError),
     FailedDesktop,
     FailedPowerControl(String),
 }
@@ -20,12 +23,16 @@ impl From<ErrorStatusMessage> for Box<str> {
         use ErrorStatusMessage::*;
 
         match err {
-            AuthenticationError(_) => "Authentication failed".into(),
-            NoGraphicalEnvironment => "No graphical environment specified".into(),
-            FailedGraphicalEnvironment => "Failed booting into the graphical environment".into(),
-            FailedDesktop => "Failed booting into desktop environment".into(),
+            AuthenticationError(_) => message(Message::AuthenticationFailed).into(),
+            NoGraphicalEnvironment => message(Message::NoGraphicalEnvironment).into(),
+            FailedGraphicalEnvironment(err) => message_with(
+                Message::FailedGraphicalEnvironment,
+                &[("reason", &err.to_string())],
+            )
+            .into(),
+            FailedDesktop => message(Message::FailedDesktop).into(),
             FailedPowerControl(name) => {
-                format!("Failed to {name}... Check the logs for more information").into()
+                message_with(Message::FailedPowerControl, &[("name", &text(&name))]).into()
             }
         }
     }
@@ -48,8 +55,8 @@ impl From<InfoStatusMessage> for Box<str> {
         use InfoStatusMessage::*;
 
         match info {
-            LoggingIn => "Authentication successful. Logging in...".into(),
-            Authenticating => "Verifying credentials".into(),
+            LoggingIn => message(Message::LoggingIn).into(),
+            Authenticating => message(Message::Authenticating).into(),
         }
     }
 }
@@ -10,6 +10,8 @@ use ratatui::{
 
 use crate::config::{get_color, get_modifiers, SwitcherConfig, SwitcherVisibility};
 
+use super::messages;
+
 #[derive(PartialEq, Eq, Debug, Clone)]
 pub struct SwitcherItem<T> {
     pub title: String,
@@ -139,6 +141,17 @@ impl<T> SwitcherWidget<T> {
         self.selector.try_select(title)
     }
 
+    /// Replace all the items while trying to keep the current selection
+    pub fn set_items(&mut self, items: Vec<SwitcherItem<T>>) {
+        let selected_title = self.selector.current().map(|item| item.title.clone());
+
+        self.selector = Switcher::new(items);
+
+        if let Some(title) = selected_title {
+            self.selector.try_select(&title);
+        }
+    }
+
     fn do_show_neighbours(&self, area_width: usize) -> bool {
         self.config.show_neighbours
             && usize::from(self.config.max_display_length) * 3
@@ -373,7 +386,7 @@ impl<T> SwitcherWidget<T> {
             }
         } else {
             spans.push(Span::styled(
-                &config.no_envs_text,
+                messages::text(&config.no_envs_text),
                 self.empty_style(is_focused),
             ));
         }